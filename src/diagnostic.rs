@@ -0,0 +1,205 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+use {
+    is_ignored_line, parse_line, read_line, split_fields, Error, ErrorType, Fstab, Result,
+    FSTAB_PATH,
+};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Severity {
+    /// Something is suspicious, but works; for a parse, the line was still read
+    Warning,
+    /// Something is wrong; for a parse, the line could not be read and was skipped
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A problem found on a line while parsing leniently
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub error: Error,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.severity)?;
+        self.error.fmt(f)
+    }
+}
+
+/// The result of a lenient parse: every entry that could be read, and the
+/// problems found on the other lines
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParseReport {
+    pub entries: Vec<Fstab>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ParseReport {
+    /// Returns `true` if any line had to be skipped
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Diagnostics for the lines that were skipped
+    pub fn errors<'a>(&'a self) -> impl Iterator<Item = &'a Error> + 'a {
+        self.with_severity(Severity::Error)
+    }
+
+    /// Diagnostics for the lines that were read but look suspicious
+    pub fn warnings<'a>(&'a self) -> impl Iterator<Item = &'a Error> + 'a {
+        self.with_severity(Severity::Warning)
+    }
+
+    fn with_severity<'a>(&'a self, severity: Severity) -> impl Iterator<Item = &'a Error> + 'a {
+        self.diagnostics
+            .iter()
+            .filter(move |d| d.severity == severity)
+            .map(|d| &d.error)
+    }
+
+    fn push(&mut self, severity: Severity, error: Error) {
+        self.diagnostics.push(Diagnostic { severity, error });
+    }
+}
+
+/// Warnings for a line that parsed successfully
+fn line_warnings(line: &str) -> Vec<Error> {
+    let mut warnings = Vec::new();
+    if let Some(&(start, options)) = split_fields(line).get(3) {
+        let mut offset = start;
+        for option in options.split(',') {
+            if option.is_empty() {
+                warnings.push(
+                    Error::new(ErrorType::EmptyOption)
+                        .with_span(offset..offset)
+                        .with_text(line),
+                );
+            }
+            offset += option.len() + 1;
+        }
+    }
+    warnings
+}
+
+/// Open a fstab file and read it leniently
+/// When `path` is set to `None`, this function will use the default path.
+///
+/// Only failing to open the file is an error; problems in the content are
+/// reported in the `ParseReport`.
+pub fn open_fstab_lenient(path: Option<&str>) -> Result<ParseReport> {
+    let path = path.unwrap_or(FSTAB_PATH);
+    let mut report =
+        read_fstab_lenient(File::open(path).map_err(|e| Error::from(e).with_path(path))?);
+    for d in &mut report.diagnostics {
+        d.error = d.error.clone().with_path(path);
+    }
+    Ok(report)
+}
+
+/// Parse the content of a fstab file leniently
+pub fn parse_fstab_lenient(content: &str) -> ParseReport {
+    read_fstab_lenient(content.as_bytes())
+}
+
+/// Read fstab content from any reader leniently
+///
+/// A line that can not be parsed is skipped and reported as an error; reading
+/// goes on with the next line. An I/O error ends the report.
+pub fn read_fstab_lenient<R: Read>(reader: R) -> ParseReport {
+    let mut reader = BufReader::new(reader);
+    let mut report = ParseReport::default();
+
+    let mut n = 0;
+    loop {
+        let l = match read_line(&mut reader, &mut n) {
+            Ok(Some(l)) => l,
+            Ok(None) => break,
+            Err(e) => {
+                let invalid_utf8 = matches!(*e.reason(), ErrorType::InvalidUtf8);
+                report.push(Severity::Error, e);
+                if invalid_utf8 {
+                    continue;
+                }
+                break;
+            }
+        };
+        if is_ignored_line(&l) {
+            continue;
+        }
+        match parse_line(&l) {
+            Ok(entry) => {
+                for w in line_warnings(&l) {
+                    report.push(Severity::Warning, w.with_line(n));
+                }
+                report.entries.push(entry);
+            }
+            Err(e) => report.push(Severity::Error, e.with_line(n)),
+        }
+    }
+    report
+}
+
+#[test]
+fn lenient_parse() {
+    let content = "proc /proc proc defaults\n\
+                   /dev/sda1 /\n\
+                   /dev/sda2 /home ext4 defaults,,noatime, 0 two\n\
+                   tmpfs /tmp tmpfs nosuid,,nodev 0 0\n\
+                   /dev/sda3 /var ext4 defaults 0 2 extra\n";
+    let report = parse_fstab_lenient(content);
+    assert_eq!(report.entries.len(), 2);
+    assert_eq!(report.entries[1].dir, "/tmp");
+    assert!(report.has_errors());
+    let errors = report.errors().map(|e| e.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            "2:12: missing field 3 (fs_vfstype)",
+            "3:43: invalid number: invalid digit found in string",
+            "5:34: too many fields: extra",
+        ]
+    );
+    let warnings = report.warnings().map(|e| e.to_string()).collect::<Vec<_>>();
+    assert_eq!(warnings, vec!["4:25: empty mount option"]);
+    assert_eq!(
+        report.diagnostics[0].to_string(),
+        "error: 2:12: missing field 3 (fs_vfstype)"
+    );
+
+    let report =
+        read_fstab_lenient(&b"/dev/sda1 /mnt/\xff ext4 defaults\nproc /proc proc defaults\n"[..]);
+    assert_eq!(report.entries.len(), 1);
+    assert_eq!(report.errors().next().unwrap().line(), Some(1));
+
+    let dir = ::test_dir("lenient");
+    let path = dir.join("fstab");
+    let path = path.to_str().unwrap();
+    ::std::fs::write(path, "proc /proc proc defaults 0 0\n/dev/sda1 /mnt\n").unwrap();
+    let report = open_fstab_lenient(Some(path)).unwrap();
+    assert_eq!(report.entries.len(), 1);
+    assert_eq!(report.diagnostics.len(), 1);
+    assert_eq!(report.diagnostics[0].error.path(), Some(path));
+    assert_eq!(report.diagnostics[0].error.line(), Some(2));
+    ::std::fs::remove_dir_all(&dir).unwrap();
+    assert!(open_fstab_lenient(Some("/nonexistent/fstab")).is_err());
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use textdiff::unified_diff;
+use {normalize_dir, Device, Fstab, FstabDocument, MountOption};
+
+/// A change to one field of an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldChange {
+    Device { old: Device, new: Device },
+    Type { old: String, new: String },
+    OptionAdded(MountOption),
+    OptionRemoved(MountOption),
+    Dump { old: bool, new: bool },
+    Fsck { old: usize, new: usize },
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FieldChange::Device { ref old, ref new } => write!(f, "device: {} -> {}", old, new),
+            FieldChange::Type { ref old, ref new } => write!(f, "type: {} -> {}", old, new),
+            FieldChange::OptionAdded(ref o) => write!(f, "option added: {}", o),
+            FieldChange::OptionRemoved(ref o) => write!(f, "option removed: {}", o),
+            FieldChange::Dump { old, new } => write!(f, "dump: {} -> {}", old as u8, new as u8),
+            FieldChange::Fsck { old, new } => write!(f, "fsck: {} -> {}", old, new),
+        }
+    }
+}
+
+/// How an entry differs between two tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryDiff {
+    /// The entry is only in the new table
+    Added(Fstab),
+    /// The entry is only in the old table
+    Removed(Fstab),
+    /// The entry is in both tables, with changes
+    Modified {
+        old: Fstab,
+        new: Fstab,
+        changes: Vec<FieldChange>,
+    },
+}
+
+impl EntryDiff {
+    /// The mount point of the entry
+    pub fn dir(&self) -> &str {
+        match *self {
+            EntryDiff::Added(ref e) | EntryDiff::Removed(ref e) => &e.dir,
+            EntryDiff::Modified { ref new, .. } => &new.dir,
+        }
+    }
+}
+
+impl fmt::Display for EntryDiff {
+    /// Writes `+ dir`, `- dir` or `~ dir` followed by the changed fields, one per line
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EntryDiff::Added(ref e) => write!(f, "+ {}: {} {}", e.dir, e.device, e.device_type),
+            EntryDiff::Removed(ref e) => write!(f, "- {}: {} {}", e.dir, e.device, e.device_type),
+            EntryDiff::Modified {
+                ref new,
+                ref changes,
+                ..
+            } => {
+                write!(f, "~ {}", new.dir)?;
+                for change in changes {
+                    write!(f, "\n    {}", change)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The differences between two tables, with entries matched by mount point
+///
+/// Mount points are compared after normalizing them, so `/home/` and `/home`
+/// are the same. Entries without a mount point path, like swap, are matched
+/// by mount point and device together. Entries that only differ in the
+/// order of their options are not modified.
+#[derive(Debug, Clone, Default)]
+pub struct TableDiff {
+    /// Removed and modified entries in the order of the old table, then added
+    /// entries in the order of the new one
+    pub entries: Vec<EntryDiff>,
+    old_text: String,
+    new_text: String,
+}
+
+impl TableDiff {
+    /// Returns `true` if the tables have the same entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// A textual unified diff of the tables, as `diff -u` shows it
+    pub fn unified(&self, old_label: &str, new_label: &str) -> String {
+        unified_diff(&self.old_text, &self.new_text, old_label, new_label)
+    }
+}
+
+impl fmt::Display for TableDiff {
+    /// Writes every entry difference, one after the other
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two tables
+pub fn diff_tables(old: &[Fstab], new: &[Fstab]) -> TableDiff {
+    let text = |list: &[Fstab]| list.iter().map(|e| format!("{}\n", e)).collect();
+    TableDiff {
+        entries: diff_entries(old, new),
+        old_text: text(old),
+        new_text: text(new),
+    }
+}
+
+/// Compare the entries of two documents
+///
+/// The unified rendering shows the whole documents, comments included.
+pub fn diff_documents(old: &FstabDocument, new: &FstabDocument) -> TableDiff {
+    TableDiff {
+        entries: diff_entries(&old.to_fstab(), &new.to_fstab()),
+        old_text: old.to_string(),
+        new_text: new.to_string(),
+    }
+}
+
+fn diff_entries(old: &[Fstab], new: &[Fstab]) -> Vec<EntryDiff> {
+    let new_keys = keys(new);
+    let mut matched = vec![false; new.len()];
+    let mut entries = Vec::new();
+    for (entry, key) in old.iter().zip(keys(old)) {
+        let found = new_keys.iter().position(|k| *k == key);
+        match found {
+            Some(i) => {
+                matched[i] = true;
+                let changes = field_changes(entry, &new[i]);
+                if !changes.is_empty() {
+                    entries.push(EntryDiff::Modified {
+                        old: entry.clone(),
+                        new: new[i].clone(),
+                        changes,
+                    });
+                }
+            }
+            None => entries.push(EntryDiff::Removed(entry.clone())),
+        }
+    }
+    for (entry, matched) in new.iter().zip(matched) {
+        if !matched {
+            entries.push(EntryDiff::Added(entry.clone()));
+        }
+    }
+    entries
+}
+
+/// The keys entries are matched by, numbered when a key is used more than once
+fn keys(list: &[Fstab]) -> Vec<(String, usize)> {
+    let mut seen = HashMap::new();
+    list.iter()
+        .map(|e| {
+            let dir = normalize_dir(&e.dir);
+            let key = if dir.starts_with('/') {
+                dir
+            } else {
+                format!("{} {}", dir, e.device)
+            };
+            let n = seen.entry(key.clone()).or_insert(0);
+            *n += 1;
+            (key, *n)
+        })
+        .collect()
+}
+
+fn field_changes(old: &Fstab, new: &Fstab) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    if old.device != new.device {
+        changes.push(FieldChange::Device {
+            old: old.device.clone(),
+            new: new.device.clone(),
+        });
+    }
+    if old.device_type != new.device_type {
+        changes.push(FieldChange::Type {
+            old: old.device_type.clone(),
+            new: new.device_type.clone(),
+        });
+    }
+    for o in &old.options {
+        if !new.options.iter().any(|n| n == o) {
+            changes.push(FieldChange::OptionRemoved(o.clone()));
+        }
+    }
+    for o in &new.options {
+        if !old.options.iter().any(|n| n == o) {
+            changes.push(FieldChange::OptionAdded(o.clone()));
+        }
+    }
+    if old.dump != new.dump {
+        changes.push(FieldChange::Dump {
+            old: old.dump,
+            new: new.dump,
+        });
+    }
+    if old.fsck != new.fsck {
+        changes.push(FieldChange::Fsck {
+            old: old.fsck,
+            new: new.fsck,
+        });
+    }
+    changes
+}
+
+#[test]
+fn diff_two_tables() {
+    let old = ::parse_fstab(concat!(
+        "UUID=0a1b / ext4 errors=remount-ro 0 1\n",
+        "UUID=2c3d /home ext4 defaults,commit=5 0 2\n",
+        "UUID=ffff none swap sw 0 0\n",
+        "/dev/sdd1 /backup xfs defaults 0 2\n",
+        "tmpfs /tmp tmpfs nosuid,nodev 0 0\n",
+    ))
+    .unwrap();
+    let new = ::parse_fstab(concat!(
+        "UUID=0a1b / ext4 errors=remount-ro 0 1\n",
+        "LABEL=home /home/ xfs defaults,commit=60,noatime 1 0\n",
+        "UUID=eeee none swap sw 0 0\n",
+        "tmpfs /tmp tmpfs nodev,nosuid 0 0\n",
+        "LABEL=data /data ext4 defaults 0 2\n",
+    ))
+    .unwrap();
+    let diff = diff_tables(&old, &new);
+    assert_eq!(diff.entries.len(), 5);
+    assert_eq!(
+        diff.entries[0],
+        EntryDiff::Modified {
+            old: old[1].clone(),
+            new: new[1].clone(),
+            changes: vec![
+                FieldChange::Device {
+                    old: Device::Uuid("2c3d".to_owned()),
+                    new: Device::Label("home".to_owned()),
+                },
+                FieldChange::Type {
+                    old: "ext4".to_owned(),
+                    new: "xfs".to_owned(),
+                },
+                FieldChange::OptionRemoved(MountOption::with_value("commit", "5")),
+                FieldChange::OptionAdded(MountOption::with_value("commit", "60")),
+                FieldChange::OptionAdded(MountOption::new("noatime")),
+                FieldChange::Dump {
+                    old: false,
+                    new: true,
+                },
+                FieldChange::Fsck { old: 2, new: 0 },
+            ],
+        }
+    );
+    assert_eq!(
+        diff.to_string(),
+        concat!(
+            "~ /home/\n",
+            "    device: UUID=2c3d -> LABEL=home\n",
+            "    type: ext4 -> xfs\n",
+            "    option removed: commit=5\n",
+            "    option added: commit=60\n",
+            "    option added: noatime\n",
+            "    dump: 0 -> 1\n",
+            "    fsck: 2 -> 0\n",
+            "- none: UUID=ffff swap\n",
+            "- /backup: /dev/sdd1 xfs\n",
+            "+ none: UUID=eeee swap\n",
+            "+ /data: LABEL=data ext4\n",
+        )
+    );
+    assert_eq!(
+        diff.unified("a/fstab", "b/fstab"),
+        concat!(
+            "--- a/fstab\n+++ b/fstab\n@@ -1,5 +1,5 @@\n",
+            " UUID=0a1b\t/\text4\terrors=remount-ro\t0\t1\n",
+            "-UUID=2c3d\t/home\text4\tdefaults,commit=5\t0\t2\n",
+            "-UUID=ffff\tnone\tswap\tsw\t0\t0\n",
+            "-/dev/sdd1\t/backup\txfs\tdefaults\t0\t2\n",
+            "-tmpfs\t/tmp\ttmpfs\tnosuid,nodev\t0\t0\n",
+            "+LABEL=home\t/home/\txfs\tdefaults,commit=60,noatime\t1\t0\n",
+            "+UUID=eeee\tnone\tswap\tsw\t0\t0\n",
+            "+tmpfs\t/tmp\ttmpfs\tnodev,nosuid\t0\t0\n",
+            "+LABEL=data\t/data\text4\tdefaults\t0\t2\n",
+        )
+    );
+    assert!(diff_tables(&old, &old).is_empty());
+
+    let doc = "# root\nUUID=0a1b / ext4 errors=remount-ro 0 1\n"
+        .parse::<FstabDocument>()
+        .unwrap();
+    let mut changed = doc.clone();
+    changed.find_mut("/").unwrap().fsck = 0;
+    let diff = diff_documents(&doc, &changed);
+    assert_eq!(diff.to_string(), "~ /\n    fsck: 1 -> 0\n");
+    assert!(diff.unified("a", "b").contains("\n # root\n"));
+}
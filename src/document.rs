@@ -0,0 +1,460 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+
+use {
+    format_fields, fstab_to_string, invalid_utf8, is_ignored_line, normalize_dir, parse_line,
+    split_fields, Error, Fstab, Result, SaveOptions, Saved, FSTAB_PATH,
+};
+
+/// An entry line of a fstab document
+///
+/// The original text of the line is kept, so an entry that is not modified
+/// is written back exactly as it was read. A modified entry reuses the
+/// whitespace of the original line between its fields.
+#[derive(Debug, Clone)]
+pub struct EntryLine {
+    raw: String,
+    original: Fstab,
+    entry: Fstab,
+}
+
+impl EntryLine {
+    fn parse(raw: &str) -> Result<EntryLine> {
+        let entry = parse_line(raw)?;
+        Ok(EntryLine {
+            raw: raw.to_owned(),
+            original: entry.clone(),
+            entry,
+        })
+    }
+
+    /// Create a new entry line, separating the fields with a single tab
+    pub fn new(entry: Fstab) -> EntryLine {
+        EntryLine {
+            raw: entry.to_string(),
+            original: entry.clone(),
+            entry,
+        }
+    }
+
+    /// Create a new entry line using the field layout of `template`
+    pub fn with_layout(entry: Fstab, template: &EntryLine) -> EntryLine {
+        EntryLine {
+            raw: render_fields(&template.raw, &format_fields(&entry)),
+            original: entry.clone(),
+            entry,
+        }
+    }
+
+    /// The parsed entry
+    pub fn entry(&self) -> &Fstab {
+        &self.entry
+    }
+
+    /// The parsed entry, for modification
+    pub fn entry_mut(&mut self) -> &mut Fstab {
+        &mut self.entry
+    }
+
+    /// The text of the line as it was read
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// Returns `true` if the entry differs from the text it was read from
+    pub fn is_modified(&self) -> bool {
+        self.entry != self.original
+    }
+}
+
+impl fmt::Display for EntryLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.is_modified() {
+            return f.write_str(&self.raw);
+        }
+        let mut fields = format_fields(&self.entry);
+        let old_count = split_fields(&self.raw).len();
+        let needed = if self.entry.fsck != 0 {
+            6
+        } else if self.entry.dump {
+            5
+        } else {
+            4
+        };
+        fields.truncate(std::cmp::max(old_count, needed));
+        f.write_str(&render_fields(&self.raw, &fields))
+    }
+}
+
+/// Lay out `fields` using the whitespace found between the fields of `raw`
+///
+/// Runs of spaces are shrunk or grown so that the following columns keep
+/// their position where possible; tabs are kept as they are.
+fn render_fields(raw: &str, fields: &[String]) -> String {
+    let old = split_fields(raw);
+    let default_sep = match old.len() {
+        0 | 1 => " ",
+        n => {
+            let prev_end = old[n - 2].0 + old[n - 2].1.len();
+            &raw[prev_end..old[n - 1].0]
+        }
+    };
+    let mut out = String::new();
+    // How many characters the text written so far is longer than the original
+    let mut shift = 0isize;
+    let mut prev_end = 0;
+    for (i, new) in fields.iter().enumerate() {
+        match old.get(i) {
+            Some(&(start, text)) => {
+                let sep = &raw[prev_end..start];
+                if i > 0 && !sep.is_empty() && sep.chars().all(|c| c == ' ') {
+                    let len = std::cmp::max(1, sep.len() as isize - shift);
+                    shift -= sep.len() as isize - len;
+                    out.push_str(&" ".repeat(len as usize));
+                } else {
+                    if sep.contains('\t') {
+                        shift = 0;
+                    }
+                    out.push_str(sep);
+                }
+                shift += new.chars().count() as isize - text.chars().count() as isize;
+                prev_end = start + text.len();
+            }
+            None => {
+                if i > 0 {
+                    out.push_str(default_sep);
+                }
+            }
+        }
+        out.push_str(new);
+    }
+    if fields.len() >= old.len() {
+        out.push_str(&raw[prev_end..]);
+    }
+    out
+}
+
+/// A line of a fstab document
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum Line {
+    /// A comment, kept verbatim including leading whitespace
+    Comment(String),
+    /// An empty or whitespace only line, kept verbatim
+    Blank(String),
+    /// A mount entry
+    Entry(EntryLine),
+}
+
+impl Line {
+    /// The entry of this line, if it is an entry line
+    pub fn entry(&self) -> Option<&Fstab> {
+        match *self {
+            Line::Entry(ref e) => Some(e.entry()),
+            _ => None,
+        }
+    }
+
+    /// The entry of this line for modification, if it is an entry line
+    pub fn entry_mut(&mut self) -> Option<&mut Fstab> {
+        match *self {
+            Line::Entry(ref mut e) => Some(e.entry_mut()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Line::Comment(ref s) | Line::Blank(ref s) => f.write_str(s),
+            Line::Entry(ref e) => e.fmt(f),
+        }
+    }
+}
+
+/// A fstab file that keeps comments, blank lines and the original layout
+///
+/// Writing a document that has not been modified with `to_string()` gives
+/// back exactly the text it was parsed from.
+#[derive(Debug, Clone, Default)]
+pub struct FstabDocument {
+    lines: Vec<Line>,
+    trailing_newline: bool,
+}
+
+impl FstabDocument {
+    /// Open a fstab file and read it into a `FstabDocument`
+    /// When `path` is set to `None`, this function will use the default path.
+    pub fn open(path: Option<&str>) -> Result<FstabDocument> {
+        let path = path.unwrap_or(FSTAB_PATH);
+        File::open(path)
+            .map_err(Error::from)
+            .and_then(FstabDocument::read)
+            .map_err(|e| e.with_path(path))
+    }
+
+    /// Write the document to a fstab file, atomically
+    /// When `path` is set to `None`, this function will use the default path.
+    ///
+    /// See [`SaveOptions`](struct.SaveOptions.html) for backups and dry runs.
+    pub fn save(&self, path: Option<&str>, options: &SaveOptions) -> Result<Saved> {
+        options.save(path.unwrap_or(FSTAB_PATH), &self.to_string())
+    }
+
+    /// Read fstab content from any reader into a `FstabDocument`
+    pub fn read<R: Read>(mut reader: R) -> Result<FstabDocument> {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+        match String::from_utf8(content) {
+            Ok(content) => content.parse(),
+            Err(e) => {
+                let bytes = e.as_bytes();
+                let valid = e.utf8_error().valid_up_to();
+                let start = bytes[..valid]
+                    .iter()
+                    .rposition(|&b| b == b'\n')
+                    .map_or(0, |i| i + 1);
+                let end = bytes[start..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(bytes.len(), |i| start + i);
+                let line = bytes[..start].iter().filter(|&&b| b == b'\n').count() + 1;
+                Err(invalid_utf8(&bytes[start..end], line))
+            }
+        }
+    }
+
+    /// All lines of the document
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// All lines of the document, for inserting, removing or reordering lines
+    pub fn lines_mut(&mut self) -> &mut Vec<Line> {
+        &mut self.lines
+    }
+
+    /// Iterate over the entries of the document
+    pub fn entries<'a>(&'a self) -> impl Iterator<Item = &'a Fstab> + 'a {
+        self.lines.iter().filter_map(Line::entry)
+    }
+
+    /// Iterate over the entries of the document for modification
+    pub fn entries_mut<'a>(&'a mut self) -> impl Iterator<Item = &'a mut Fstab> + 'a {
+        self.lines.iter_mut().filter_map(Line::entry_mut)
+    }
+
+    /// Find the first entry mounted at `dir`, comparing normalized paths so
+    /// that `/home/` finds `/home`
+    pub fn find(&self, dir: &str) -> Option<&Fstab> {
+        let dir = normalize_dir(dir);
+        self.entries().find(|e| normalize_dir(&e.dir) == dir)
+    }
+
+    /// Find the first entry mounted at `dir` for modification
+    pub fn find_mut(&mut self, dir: &str) -> Option<&mut Fstab> {
+        let dir = normalize_dir(dir);
+        self.entries_mut().find(|e| normalize_dir(&e.dir) == dir)
+    }
+
+    /// Append an entry, laid out like the last entry of the document
+    pub fn push(&mut self, entry: Fstab) {
+        let line = match self.lines.iter().rev().find_map(|l| match *l {
+            Line::Entry(ref e) => Some(e),
+            _ => None,
+        }) {
+            Some(template) => EntryLine::with_layout(entry, template),
+            None => EntryLine::new(entry),
+        };
+        if self.lines.is_empty() {
+            self.trailing_newline = true;
+        }
+        self.lines.push(Line::Entry(line));
+    }
+
+    /// Remove the first entry mounted at `dir` and return it
+    pub fn remove(&mut self, dir: &str) -> Option<Fstab> {
+        let dir = normalize_dir(dir);
+        let pos = self
+            .lines
+            .iter()
+            .position(|l| l.entry().map_or(false, |e| normalize_dir(&e.dir) == dir))?;
+        match self.lines.remove(pos) {
+            Line::Entry(e) => Some(e.entry),
+            _ => None,
+        }
+    }
+
+    /// Rewrite every entry line with the fields aligned in columns
+    ///
+    /// Comments and blank lines are kept as they are, and so are the line endings.
+    pub fn align(&mut self) {
+        let aligned = fstab_to_string(&self.to_fstab());
+        let mut aligned = aligned.lines();
+        for line in self.lines.iter_mut() {
+            if let Line::Entry(ref mut e) = *line {
+                let mut raw = aligned.next().unwrap_or_default().to_owned();
+                if e.raw.ends_with('\r') {
+                    raw.push('\r');
+                }
+                e.raw = raw;
+                e.original = e.entry.clone();
+            }
+        }
+    }
+
+    /// Copy the entries into a list of `Fstab`
+    pub fn to_fstab(&self) -> Vec<Fstab> {
+        self.entries().cloned().collect()
+    }
+}
+
+impl FromStr for FstabDocument {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<FstabDocument> {
+        let mut text = s;
+        let trailing_newline = text.ends_with('\n');
+        if trailing_newline {
+            text = &text[..text.len() - 1];
+        }
+        let mut lines = Vec::new();
+        if !s.is_empty() {
+            for (n, l) in text.split('\n').enumerate() {
+                lines.push(if l.trim().is_empty() {
+                    Line::Blank(l.to_owned())
+                } else if is_ignored_line(l) {
+                    Line::Comment(l.to_owned())
+                } else {
+                    Line::Entry(EntryLine::parse(l).map_err(|e| e.with_line(n + 1))?)
+                });
+            }
+        }
+        Ok(FstabDocument {
+            lines,
+            trailing_newline,
+        })
+    }
+}
+
+impl fmt::Display for FstabDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, l) in self.lines.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            l.fmt(f)?;
+        }
+        if self.trailing_newline {
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+const SAMPLE: &str = "# /etc/fstab: static file system information.\n\
+                      #\n\
+                      # <file system>  <mount point>  <type>  <options>          <dump> <pass>\n\
+                      UUID=5b2e1c2a    /              ext4    errors=remount-ro  0      1\n\
+                      \n\
+                      \t# swap was on /dev/sda5 during installation\n\
+                      UUID=0c1d2e3f\tnone\tswap\tsw\t0\t0\n\
+                      /dev/sr0         /media/cdrom0  udf     user,noauto        0      0   \n\
+                      tmpfs /tmp tmpfs defaults\r\n";
+
+#[test]
+fn document_round_trip() {
+    let doc = SAMPLE.parse::<FstabDocument>().unwrap();
+    assert_eq!(doc.to_string(), SAMPLE);
+    assert_eq!(doc.entries().count(), 4);
+    assert_eq!(doc.lines().len(), 9);
+
+    let doc = "tmpfs /tmp tmpfs defaults 0 0".parse::<FstabDocument>().unwrap();
+    assert_eq!(doc.to_string(), "tmpfs /tmp tmpfs defaults 0 0");
+}
+
+#[test]
+fn document_modify_keeps_layout() {
+    let mut doc = SAMPLE.parse::<FstabDocument>().unwrap();
+    doc.find_mut("/media/cdrom0").unwrap().options = "ro".into();
+    doc.find_mut("none").unwrap().fsck = 2;
+    doc.find_mut("/tmp").unwrap().dump = true;
+    let expected = SAMPLE
+        .replace(
+            "/dev/sr0         /media/cdrom0  udf     user,noauto        0      0   ",
+            "/dev/sr0         /media/cdrom0  udf     ro                 0      0   ",
+        )
+        .replace("sw\t0\t0", "sw\t0\t2")
+        .replace("tmpfs /tmp tmpfs defaults\r", "tmpfs /tmp tmpfs defaults 1\r");
+    assert_eq!(doc.to_string(), expected);
+}
+
+#[test]
+fn document_push_remove() {
+    let mut doc = SAMPLE.parse::<FstabDocument>().unwrap();
+    let root = doc.remove("/").unwrap();
+    assert_eq!(root.fsck, 1);
+    assert!(doc.find("/").is_none());
+    doc.push(root);
+    assert!(doc
+        .to_string()
+        .ends_with("\ntmpfs /tmp tmpfs defaults\r\nUUID=5b2e1c2a / ext4 errors=remount-ro 0 1\r\n"));
+
+    // Mount points are compared normalized
+    assert_eq!(doc.find("//media/cdrom0/").unwrap().dir, "/media/cdrom0");
+    doc.find_mut("/tmp/.").unwrap().fsck = 2;
+    assert_eq!(doc.remove("/tmp/").unwrap().fsck, 2);
+    assert!(doc.find("/tmp").is_none());
+
+    let mut doc = FstabDocument::default();
+    doc.push(root_entry());
+    assert_eq!(doc.to_string(), "UUID=5b2e1c2a\t/\text4\terrors=remount-ro\t0\t1\n");
+}
+
+#[test]
+fn document_align() {
+    let mut doc = SAMPLE.parse::<FstabDocument>().unwrap();
+    doc.find_mut("/tmp").unwrap().options = "size=1G".into();
+    doc.align();
+    let expected = concat!(
+        "# /etc/fstab: static file system information.\n",
+        "#\n",
+        "# <file system>  <mount point>  <type>  <options>          <dump> <pass>\n",
+        "UUID=5b2e1c2a /             ext4  errors=remount-ro 0 1\n",
+        "\n",
+        "\t# swap was on /dev/sda5 during installation\n",
+        "UUID=0c1d2e3f none          swap  sw                0 0\n",
+        "/dev/sr0      /media/cdrom0 udf   user,noauto       0 0\n",
+        "tmpfs         /tmp          tmpfs size=1G           0 0\r\n",
+    );
+    assert_eq!(doc.to_string(), expected);
+    assert!(doc.lines().iter().all(|l| match *l {
+        Line::Entry(ref e) => !e.is_modified(),
+        _ => true,
+    }));
+}
+
+#[test]
+fn document_invalid_utf8() {
+    let e = FstabDocument::read(&b"# comment\nproc /proc proc defaults\n/dev/sda1 /mnt/\xff ext4\n"[..])
+        .unwrap_err();
+    assert_eq!(e.line(), Some(3));
+    assert_eq!(e.columns(), Some(16..17));
+    assert_eq!(e.text(), Some("/dev/sda1 /mnt/\u{fffd} ext4"));
+}
+
+#[cfg(test)]
+fn root_entry() -> Fstab {
+    Fstab {
+        device: ::Device::Uuid("5b2e1c2a".to_owned()),
+        dir: "/".to_owned(),
+        device_type: "ext4".to_owned(),
+        options: "errors=remount-ro".into(),
+        dump: false,
+        fsck: 1,
+    }
+}
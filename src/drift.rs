@@ -0,0 +1,224 @@
+use std::fmt;
+
+use {normalize_dir, open_fstab, open_mounts, Environment, Fstab, MountFlags, Resolver, Result};
+
+/// A difference between a configured entry and the mount table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    /// The entry is configured but nothing is mounted at its mount point
+    NotMounted(Fstab),
+    /// Something is mounted that no entry configures
+    NotConfigured(Fstab),
+    /// The mount point has another device mounted than configured
+    Device { configured: Fstab, mounted: Fstab },
+    /// The filesystem is mounted with another type than configured
+    Type { configured: Fstab, mounted: Fstab },
+    /// The filesystem is mounted with other flags than the configured options give
+    Options {
+        configured: Fstab,
+        mounted: Fstab,
+        /// The flags that differ
+        flags: MountFlags,
+    },
+}
+
+impl Drift {
+    /// The mount point the drift is about
+    pub fn dir(&self) -> &str {
+        match *self {
+            Drift::NotMounted(ref e) | Drift::NotConfigured(ref e) => &e.dir,
+            Drift::Device { ref configured, .. }
+            | Drift::Type { ref configured, .. }
+            | Drift::Options { ref configured, .. } => &configured.dir,
+        }
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.dir())?;
+        match *self {
+            Drift::NotMounted(_) => f.write_str("configured but not mounted"),
+            Drift::NotConfigured(ref m) => write!(f, "{} mounted but not configured", m.device),
+            Drift::Device {
+                ref configured,
+                ref mounted,
+            } => write!(
+                f,
+                "{} mounted, {} configured",
+                mounted.device, configured.device
+            ),
+            Drift::Type {
+                ref configured,
+                ref mounted,
+            } => write!(
+                f,
+                "mounted as {}, configured as {}",
+                mounted.device_type, configured.device_type
+            ),
+            Drift::Options { flags, .. } => write!(f, "mounted with other flags: {}", flags),
+        }
+    }
+}
+
+/// Compare `/etc/fstab` with `/proc/self/mounts` on the running system
+pub fn detect_drift() -> Result<Vec<Drift>> {
+    Ok(drift(
+        &open_fstab(None)?,
+        &open_mounts(None)?,
+        &Resolver::new(),
+    ))
+}
+
+/// Compare configured entries with the mounted ones, matched by mount point
+///
+/// Devices are compared after resolving both through `env`, so `UUID=...`
+/// and the device node it names are the same device. Options are compared
+/// by the mount flags that the configured options set or clear; other
+/// flags the kernel reports, like the default `relatime`, are ignored.
+///
+/// Swap entries are not compared, `noauto` entries may be unmounted, and an
+/// `x-systemd.automount` entry may be mounted as `autofs`. Mounts of pseudo
+/// filesystems (`proc`, `tmpfs`, `cgroup2`, ...) need no entry.
+pub fn drift<E: Environment + ?Sized>(
+    configured: &[Fstab],
+    mounted: &[Fstab],
+    env: &E,
+) -> Vec<Drift> {
+    let mut drifts = Vec::new();
+    let mut configured_dirs = Vec::new();
+    for entry in configured {
+        let dir = normalize_dir(&entry.dir);
+        if entry.device_type == "swap" || !dir.starts_with('/') {
+            continue;
+        }
+        configured_dirs.push(dir.clone());
+        // The last mount at a mount point hides the ones before it
+        let live = match mounted.iter().rev().find(|m| normalize_dir(&m.dir) == dir) {
+            Some(live) => live,
+            None => {
+                if !entry.options.contains("noauto") {
+                    drifts.push(Drift::NotMounted(entry.clone()));
+                }
+                continue;
+            }
+        };
+        if live.device_type == "autofs" && entry.options.contains("x-systemd.automount") {
+            continue;
+        }
+        let bind = entry.options.contains("bind") || entry.options.contains("rbind");
+        if !bind && !same_device(entry, live, env) {
+            drifts.push(Drift::Device {
+                configured: entry.clone(),
+                mounted: live.clone(),
+            });
+        }
+        if !bind && !same_type(&entry.device_type, &live.device_type) {
+            drifts.push(Drift::Type {
+                configured: entry.clone(),
+                mounted: live.clone(),
+            });
+        }
+        // Flags of the mount(2) call itself are not reported for a mounted filesystem
+        let call = MountFlags::REMOUNT | MountFlags::BIND | MountFlags::MOVE | MountFlags::REC;
+        let wanted = entry.options.effective().flags;
+        let actual = live.options.effective().flags;
+        let flags =
+            ((wanted & !actual) | (actual & !wanted)) & entry.options.mentioned_flags() & !call;
+        if !flags.is_empty() {
+            drifts.push(Drift::Options {
+                configured: entry.clone(),
+                mounted: live.clone(),
+                flags,
+            });
+        }
+    }
+    for live in mounted {
+        let dir = normalize_dir(&live.dir);
+        if live.device.is_pseudo() || !dir.starts_with('/') || configured_dirs.contains(&dir) {
+            continue;
+        }
+        configured_dirs.push(dir);
+        drifts.push(Drift::NotConfigured(live.clone()));
+    }
+    drifts
+}
+
+/// Returns `true` if both entries name the same device
+fn same_device<E: Environment + ?Sized>(configured: &Fstab, mounted: &Fstab, env: &E) -> bool {
+    if configured.device == mounted.device {
+        return true;
+    }
+    match (
+        env.resolve(&configured.device),
+        env.resolve(&mounted.device),
+    ) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => configured.device.to_string() == mounted.device.to_string(),
+    }
+}
+
+/// Returns `true` if the mounted type is one the configured type allows
+///
+/// `auto` allows any type, a list allows any of its types, and `nfs`
+/// allows `nfs4`, which is what the kernel reports for NFSv4 mounts.
+fn same_type(configured: &str, mounted: &str) -> bool {
+    configured.split(',').any(|t| {
+        t == "auto"
+            || t == mounted
+            || (t == "nfs" && mounted == "nfs4")
+            || (t == "fuse" && mounted.starts_with("fuse."))
+    })
+}
+
+#[test]
+fn drift_entries() {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    let root = ::test_dir("drift");
+    fs::create_dir_all(root.join("dev/disk/by-uuid")).unwrap();
+    fs::write(root.join("dev/sda1"), b"").unwrap();
+    fs::write(root.join("dev/sda2"), b"").unwrap();
+    symlink("../../sda1", root.join("dev/disk/by-uuid/0a1b")).unwrap();
+    symlink("../../sda2", root.join("dev/disk/by-uuid/2c3d")).unwrap();
+
+    let configured = ::parse_fstab(concat!(
+        "UUID=0a1b / ext4 defaults 0 1\n",
+        "UUID=2c3d /home ext4 noatime,nosuid 0 2\n",
+        "/dev/sdc1 /media/usb vfat noauto 0 0\n",
+        "/dev/sdd1 /backup xfs defaults 0 2\n",
+        "UUID=ffff none swap sw 0 0\n",
+        "/srv/data /export/data none bind 0 0\n",
+        "LABEL=data /data auto ro,x-systemd.automount 0 0\n",
+        "nas:/vol /mnt/nas nfs defaults 0 0\n",
+    ))
+    .unwrap();
+    let mounted = ::parse_fstab(concat!(
+        "/dev/sda1 / ext4 rw,relatime,errors=remount-ro 0 0\n",
+        "proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0\n",
+        "/dev/sda1 /home xfs rw,nosuid,relatime 0 0\n",
+        "/dev/sda1 /export/data ext4 rw,relatime 0 0\n",
+        "systemd-1 /data autofs rw,relatime,fd=45 0 0\n",
+        "nas:/vol /mnt/nas nfs4 rw,relatime,vers=4.2 0 0\n",
+        "/dev/sde1 /mnt/stick vfat rw 0 0\n",
+    ))
+    .unwrap();
+
+    let drifts = drift(&configured, &mounted, &Resolver::with_root(&root))
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        drifts,
+        vec![
+            "/home: /dev/sda1 mounted, UUID=2c3d configured",
+            "/home: mounted as xfs, configured as ext4",
+            "/home: mounted with other flags: MS_NOATIME",
+            "/backup: configured but not mounted",
+            "/mnt/stick: /dev/sde1 mounted but not configured",
+        ]
+    );
+    assert!(drift(&configured[..1], &mounted[..1], &Resolver::with_root(&root)).is_empty());
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,229 @@
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Names of the fields of a fstab line, as in fstab(5)
+const FIELD_NAMES: [&str; 6] = [
+    "fs_spec",
+    "fs_file",
+    "fs_vfstype",
+    "fs_mntops",
+    "fs_freq",
+    "fs_passno",
+];
+
+/// Type of errors from reading and parsing
+///
+/// With the `serde` feature, the type is serialized as `{"kind": "field_not_exist", "detail": 2}`,
+/// with `detail` left out for the variants that carry nothing.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "kind", content = "detail", rename_all = "snake_case")
+)]
+#[non_exhaustive]
+pub enum ErrorType {
+///   `fstab` file does not exist at the given path
+    FstabNotExist(String),
+///   Permission to read the file was denied
+    PermissionDenied(String),
+///   A line is not valid UTF-8
+    InvalidUtf8,
+///   Any other I/O error
+    Io(String),
+///   The numbers in `dump` and `fsck` fields are incorrect
+    NumParseError(String),
+///   Required fields(UUID, device type, etc.) do not exist
+    FieldNotExist(usize),
+///   Extra failds after `fsck`
+    TooManyFields(String),
+///   An empty item in the mount options, such as in `defaults,,noatime`
+    EmptyOption,
+///   A line of `/proc/self/mountinfo` does not have the expected layout
+    MalformedMountInfo(String),
+}
+
+impl fmt::Display for ErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorType::FstabNotExist(ref e) => write!(f, "can not open fstab: {}", e),
+            ErrorType::PermissionDenied(ref e) => write!(f, "permission denied: {}", e),
+            ErrorType::InvalidUtf8 => f.write_str("line is not valid UTF-8"),
+            ErrorType::Io(ref e) => write!(f, "I/O error: {}", e),
+            ErrorType::NumParseError(ref e) => write!(f, "invalid number: {}", e),
+            ErrorType::FieldNotExist(i) => match FIELD_NAMES.get(i) {
+                Some(name) => write!(f, "missing field {} ({})", i + 1, name),
+                None => write!(f, "missing field {}", i + 1),
+            },
+            ErrorType::TooManyFields(ref s) => write!(f, "too many fields: {}", s),
+            ErrorType::EmptyOption => f.write_str("empty mount option"),
+            ErrorType::MalformedMountInfo(ref s) => write!(f, "malformed mountinfo: {}", s),
+        }
+    }
+}
+
+/// An error, with the location it was found at when it comes from parsing
+///
+/// `Display` writes `path:line:column: message`, leaving out the parts that
+/// are not known. The alternate form (`{:#}`) also shows the offending line
+/// with the span underlined.
+///
+/// With the `serde` feature, an error is serialized as its `reason` and the
+/// known parts of its location (`path`, `line`, `span` and `text`). The
+/// underlying I/O error is not kept.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Error {
+    reason: ErrorType,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    location: Option<Box<Location>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    source: Option<Arc<io::Error>>,
+}
+
+/// Where an error was found
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+struct Location {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    path: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    line: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    span: Option<Range<usize>>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    text: Option<String>,
+}
+
+impl Error {
+    pub(crate) fn new(reason: ErrorType) -> Error {
+        Error {
+            reason,
+            location: None,
+            source: None,
+        }
+    }
+
+    fn location_mut(&mut self) -> &mut Location {
+        self.location.get_or_insert_with(Box::default)
+    }
+
+    pub(crate) fn with_path(mut self, path: &str) -> Error {
+        self.location_mut().path = Some(path.to_owned());
+        self
+    }
+
+    pub(crate) fn with_line(mut self, line: usize) -> Error {
+        self.location_mut().line = Some(line);
+        self
+    }
+
+    pub(crate) fn with_span(mut self, span: Range<usize>) -> Error {
+        self.location_mut().span = Some(span);
+        self
+    }
+
+    pub(crate) fn with_text(mut self, text: &str) -> Error {
+        self.location_mut().text = Some(text.to_owned());
+        self
+    }
+
+    /// The kind of the error
+    pub fn reason(&self) -> &ErrorType {
+        &self.reason
+    }
+
+    /// Path of the file the error was found in
+    pub fn path(&self) -> Option<&str> {
+        self.location.as_ref()?.path.as_deref()
+    }
+
+    /// 1-based number of the line the error was found at
+    pub fn line(&self) -> Option<usize> {
+        self.location.as_ref()?.line
+    }
+
+    /// Byte range of the offending part within `text()`
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.location.as_ref()?.span.clone()
+    }
+
+    /// 1-based column range of the offending part within `text()`, in characters
+    pub fn columns(&self) -> Option<Range<usize>> {
+        let (span, text) = (self.span()?, self.text()?);
+        let start = text.get(..span.start)?.chars().count() + 1;
+        let len = text.get(span)?.chars().count();
+        Some(start..start + len)
+    }
+
+    /// The raw line the error was found in
+    pub fn text(&self) -> Option<&str> {
+        self.location.as_ref()?.text.as_deref()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let columns = self.columns();
+        if let Some(path) = self.path() {
+            write!(f, "{}:", path)?;
+        }
+        if let Some(line) = self.line() {
+            write!(f, "{}:", line)?;
+            if let Some(ref c) = columns {
+                write!(f, "{}:", c.start)?;
+            }
+        }
+        if self.path().is_some() || self.line().is_some() {
+            f.write_str(" ")?;
+        }
+        write!(f, "{}", self.reason)?;
+        if let (true, Some(text), Some(c)) = (f.alternate(), self.text(), columns) {
+            // Keep tabs in the indentation so the carets line up with the text
+            let indent = text
+                .chars()
+                .take(c.start - 1)
+                .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            write!(
+                f,
+                "\n    {}\n    {}{}",
+                text,
+                indent,
+                "^".repeat(std::cmp::max(1, c.end - c.start))
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        let reason = match e.kind() {
+            io::ErrorKind::NotFound => ErrorType::FstabNotExist(e.to_string()),
+            io::ErrorKind::PermissionDenied => ErrorType::PermissionDenied(e.to_string()),
+            _ => ErrorType::Io(e.to_string()),
+        };
+        Error {
+            source: Some(Arc::new(e)),
+            ..Error::new(reason)
+        }
+    }
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(e: std::num::ParseIntError) -> Self {
+        Error::new(ErrorType::NumParseError(e.to_string()))
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| &**e as &(dyn std::error::Error + 'static))
+    }
+}
@@ -0,0 +1,348 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not};
+
+use {MountOption, MountOptions, OptionKind};
+
+/// The `mountflags` argument of mount(2)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MountFlags(u64);
+
+impl MountFlags {
+    pub const RDONLY: MountFlags = MountFlags(1);
+    pub const NOSUID: MountFlags = MountFlags(1 << 1);
+    pub const NODEV: MountFlags = MountFlags(1 << 2);
+    pub const NOEXEC: MountFlags = MountFlags(1 << 3);
+    pub const SYNCHRONOUS: MountFlags = MountFlags(1 << 4);
+    pub const REMOUNT: MountFlags = MountFlags(1 << 5);
+    pub const MANDLOCK: MountFlags = MountFlags(1 << 6);
+    pub const DIRSYNC: MountFlags = MountFlags(1 << 7);
+    pub const NOSYMFOLLOW: MountFlags = MountFlags(1 << 8);
+    pub const NOATIME: MountFlags = MountFlags(1 << 10);
+    pub const NODIRATIME: MountFlags = MountFlags(1 << 11);
+    pub const BIND: MountFlags = MountFlags(1 << 12);
+    pub const MOVE: MountFlags = MountFlags(1 << 13);
+    pub const REC: MountFlags = MountFlags(1 << 14);
+    pub const SILENT: MountFlags = MountFlags(1 << 15);
+    pub const UNBINDABLE: MountFlags = MountFlags(1 << 17);
+    pub const PRIVATE: MountFlags = MountFlags(1 << 18);
+    pub const SLAVE: MountFlags = MountFlags(1 << 19);
+    pub const SHARED: MountFlags = MountFlags(1 << 20);
+    pub const RELATIME: MountFlags = MountFlags(1 << 21);
+    pub const I_VERSION: MountFlags = MountFlags(1 << 23);
+    pub const STRICTATIME: MountFlags = MountFlags(1 << 24);
+    pub const LAZYTIME: MountFlags = MountFlags(1 << 25);
+
+    /// No flags
+    pub fn empty() -> MountFlags {
+        MountFlags(0)
+    }
+
+    /// Flags from the raw value of mount(2)
+    pub fn from_bits(bits: u64) -> MountFlags {
+        MountFlags(bits)
+    }
+
+    /// The raw value for mount(2)
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Returns `true` if no flag is set
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all flags of `other` are set
+    pub fn contains(self, other: MountFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Set the flags of `other`
+    pub fn insert(&mut self, other: MountFlags) {
+        self.0 |= other.0;
+    }
+
+    /// Clear the flags of `other`
+    pub fn remove(&mut self, other: MountFlags) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for MountFlags {
+    type Output = MountFlags;
+
+    fn bitor(self, other: MountFlags) -> MountFlags {
+        MountFlags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for MountFlags {
+    fn bitor_assign(&mut self, other: MountFlags) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for MountFlags {
+    type Output = MountFlags;
+
+    fn bitand(self, other: MountFlags) -> MountFlags {
+        MountFlags(self.0 & other.0)
+    }
+}
+
+impl Not for MountFlags {
+    type Output = MountFlags;
+
+    fn not(self) -> MountFlags {
+        MountFlags(!self.0)
+    }
+}
+
+impl fmt::Display for MountFlags {
+    /// Writes the flags with their `MS_` names, separated by `|`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rest = *self;
+        let mut first = true;
+        for &(flag, name) in FLAG_NAMES {
+            if rest.contains(flag) {
+                f.write_str(if first { "" } else { "|" })?;
+                write!(f, "MS_{}", name)?;
+                rest.remove(flag);
+                first = false;
+            }
+        }
+        if !rest.is_empty() || first {
+            f.write_str(if first { "" } else { "|" })?;
+            write!(f, "{:#x}", rest.0)?;
+        }
+        Ok(())
+    }
+}
+
+const FLAG_NAMES: &[(MountFlags, &str)] = &[
+    (MountFlags::RDONLY, "RDONLY"),
+    (MountFlags::NOSUID, "NOSUID"),
+    (MountFlags::NODEV, "NODEV"),
+    (MountFlags::NOEXEC, "NOEXEC"),
+    (MountFlags::SYNCHRONOUS, "SYNCHRONOUS"),
+    (MountFlags::REMOUNT, "REMOUNT"),
+    (MountFlags::MANDLOCK, "MANDLOCK"),
+    (MountFlags::DIRSYNC, "DIRSYNC"),
+    (MountFlags::NOSYMFOLLOW, "NOSYMFOLLOW"),
+    (MountFlags::NOATIME, "NOATIME"),
+    (MountFlags::NODIRATIME, "NODIRATIME"),
+    (MountFlags::BIND, "BIND"),
+    (MountFlags::MOVE, "MOVE"),
+    (MountFlags::REC, "REC"),
+    (MountFlags::SILENT, "SILENT"),
+    (MountFlags::UNBINDABLE, "UNBINDABLE"),
+    (MountFlags::PRIVATE, "PRIVATE"),
+    (MountFlags::SLAVE, "SLAVE"),
+    (MountFlags::SHARED, "SHARED"),
+    (MountFlags::RELATIME, "RELATIME"),
+    (MountFlags::I_VERSION, "I_VERSION"),
+    (MountFlags::STRICTATIME, "STRICTATIME"),
+    (MountFlags::LAZYTIME, "LAZYTIME"),
+];
+
+/// What a generic option does to the mount flags: (option, flags to set, flags to clear)
+///
+/// This follows the option map of libmount; `user` and friends imply
+/// `nosuid,nodev` (and `noexec`) unless overridden by later options.
+const FLAG_OPTIONS: &[(&str, MountFlags, MountFlags)] = &[
+    ("ro", MountFlags::RDONLY, MountFlags(0)),
+    ("rw", MountFlags(0), MountFlags::RDONLY),
+    ("nosuid", MountFlags::NOSUID, MountFlags(0)),
+    ("suid", MountFlags(0), MountFlags::NOSUID),
+    ("nodev", MountFlags::NODEV, MountFlags(0)),
+    ("dev", MountFlags(0), MountFlags::NODEV),
+    ("noexec", MountFlags::NOEXEC, MountFlags(0)),
+    ("exec", MountFlags(0), MountFlags::NOEXEC),
+    ("sync", MountFlags::SYNCHRONOUS, MountFlags(0)),
+    ("async", MountFlags(0), MountFlags::SYNCHRONOUS),
+    ("remount", MountFlags::REMOUNT, MountFlags(0)),
+    ("mand", MountFlags::MANDLOCK, MountFlags(0)),
+    ("nomand", MountFlags(0), MountFlags::MANDLOCK),
+    ("dirsync", MountFlags::DIRSYNC, MountFlags(0)),
+    ("nosymfollow", MountFlags::NOSYMFOLLOW, MountFlags(0)),
+    ("symfollow", MountFlags(0), MountFlags::NOSYMFOLLOW),
+    ("noatime", MountFlags::NOATIME, MountFlags(0)),
+    ("atime", MountFlags(0), MountFlags::NOATIME),
+    ("nodiratime", MountFlags::NODIRATIME, MountFlags(0)),
+    ("diratime", MountFlags(0), MountFlags::NODIRATIME),
+    ("bind", MountFlags::BIND, MountFlags(0)),
+    (
+        "rbind",
+        MountFlags(MountFlags::BIND.0 | MountFlags::REC.0),
+        MountFlags(0),
+    ),
+    ("move", MountFlags::MOVE, MountFlags(0)),
+    ("silent", MountFlags::SILENT, MountFlags(0)),
+    ("loud", MountFlags(0), MountFlags::SILENT),
+    ("relatime", MountFlags::RELATIME, MountFlags(0)),
+    ("norelatime", MountFlags(0), MountFlags::RELATIME),
+    ("iversion", MountFlags::I_VERSION, MountFlags(0)),
+    ("noiversion", MountFlags(0), MountFlags::I_VERSION),
+    ("strictatime", MountFlags::STRICTATIME, MountFlags(0)),
+    ("nostrictatime", MountFlags(0), MountFlags::STRICTATIME),
+    ("lazytime", MountFlags::LAZYTIME, MountFlags(0)),
+    ("nolazytime", MountFlags(0), MountFlags::LAZYTIME),
+    (
+        "defaults",
+        MountFlags(0),
+        MountFlags(
+            MountFlags::RDONLY.0
+                | MountFlags::NOSUID.0
+                | MountFlags::NODEV.0
+                | MountFlags::NOEXEC.0
+                | MountFlags::SYNCHRONOUS.0,
+        ),
+    ),
+    (
+        "user",
+        MountFlags(MountFlags::NOSUID.0 | MountFlags::NODEV.0 | MountFlags::NOEXEC.0),
+        MountFlags(0),
+    ),
+    (
+        "users",
+        MountFlags(MountFlags::NOSUID.0 | MountFlags::NODEV.0 | MountFlags::NOEXEC.0),
+        MountFlags(0),
+    ),
+    (
+        "owner",
+        MountFlags(MountFlags::NOSUID.0 | MountFlags::NODEV.0),
+        MountFlags(0),
+    ),
+    (
+        "group",
+        MountFlags(MountFlags::NOSUID.0 | MountFlags::NODEV.0),
+        MountFlags(0),
+    ),
+];
+
+/// Propagation options, which mount(2) only accepts in a call of their own
+const PROPAGATION_OPTIONS: &[(&str, MountFlags)] = &[
+    ("shared", MountFlags::SHARED),
+    (
+        "rshared",
+        MountFlags(MountFlags::SHARED.0 | MountFlags::REC.0),
+    ),
+    ("slave", MountFlags::SLAVE),
+    (
+        "rslave",
+        MountFlags(MountFlags::SLAVE.0 | MountFlags::REC.0),
+    ),
+    ("private", MountFlags::PRIVATE),
+    (
+        "rprivate",
+        MountFlags(MountFlags::PRIVATE.0 | MountFlags::REC.0),
+    ),
+    ("unbindable", MountFlags::UNBINDABLE),
+    (
+        "runbindable",
+        MountFlags(MountFlags::UNBINDABLE.0 | MountFlags::REC.0),
+    ),
+];
+
+/// Generic options that are passed to the kernel in the data string
+const DATA_OPTIONS: &[&str] = &["context", "fscontext", "defcontext", "rootcontext"];
+
+/// Mount options translated for mount(2)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EffectiveOptions {
+    /// The `mountflags` argument
+    pub flags: MountFlags,
+    /// Propagation flags (`MS_SHARED`, `MS_PRIVATE`, ...), possibly with `MS_REC`
+    ///
+    /// The kernel only accepts these in a separate mount(2) call on the
+    /// mounted target.
+    pub propagation: MountFlags,
+    /// The `data` argument: the filesystem specific options, comma separated
+    pub data: String,
+}
+
+impl MountOptions {
+    /// Translate the options into mount(2) flags and the filesystem data string
+    ///
+    /// Options are applied in order, so later options override earlier ones
+    /// (`ro,rw` is read-write) and `defaults` resets the flags it covers.
+    /// Options meant for userspace only (`noauto`, `nofail`, `_netdev`,
+    /// `x-*`, ...) are left out.
+    pub fn effective(&self) -> EffectiveOptions {
+        let mut effective = EffectiveOptions::default();
+        let mut data = Vec::new();
+        for option in self {
+            if let Some(&(_, set, clear)) = FLAG_OPTIONS
+                .iter()
+                .find(|&&(name, _, _)| option.value.is_none() && name == option.name)
+            {
+                effective.flags.remove(clear);
+                effective.flags.insert(set);
+            } else if let Some(&(_, flags)) = PROPAGATION_OPTIONS
+                .iter()
+                .find(|&&(name, _)| option.value.is_none() && name == option.name)
+            {
+                effective.propagation = flags;
+            } else if is_data_option(option) {
+                data.push(option.to_string());
+            }
+        }
+        effective.data = data.join(",");
+        effective
+    }
+
+    /// The flags that the options set or clear, whatever their final state
+    pub(crate) fn mentioned_flags(&self) -> MountFlags {
+        self.iter()
+            .filter(|o| o.value.is_none())
+            .filter_map(|o| FLAG_OPTIONS.iter().find(|&&(name, _, _)| name == o.name))
+            .fold(MountFlags::empty(), |acc, &(_, set, clear)| acc | set | clear)
+    }
+}
+
+fn is_data_option(option: &MountOption) -> bool {
+    match option.kind() {
+        OptionKind::Filesystem => true,
+        OptionKind::Generic => DATA_OPTIONS.contains(&option.name.as_str()),
+        OptionKind::UserSpace => false,
+    }
+}
+
+#[test]
+fn effective_options() {
+    let e = MountOptions::from("defaults").effective();
+    assert_eq!(e, EffectiveOptions::default());
+
+    let e = MountOptions::from("ro,nosuid,nodev,noexec,noatime,errors=remount-ro,nofail,x-systemd.automount,_netdev,noauto")
+        .effective();
+    assert_eq!(
+        e.flags,
+        MountFlags::RDONLY
+            | MountFlags::NOSUID
+            | MountFlags::NODEV
+            | MountFlags::NOEXEC
+            | MountFlags::NOATIME
+    );
+    assert_eq!(e.data, "errors=remount-ro");
+
+    let e = MountOptions::from(
+        "ro,nosuid,defaults,rw,noexec,exec,sync,uid=1000,context=\"system_u:object_r:tmp_t\"",
+    )
+    .effective();
+    assert_eq!(e.flags, MountFlags::SYNCHRONOUS);
+    assert_eq!(e.data, "uid=1000,context=\"system_u:object_r:tmp_t\"");
+
+    let e = MountOptions::from("user,exec").effective();
+    assert_eq!(e.flags, MountFlags::NOSUID | MountFlags::NODEV);
+
+    let e = MountOptions::from("rbind,rslave,ro").effective();
+    assert_eq!(
+        e.flags,
+        MountFlags::BIND | MountFlags::REC | MountFlags::RDONLY
+    );
+    assert_eq!(e.propagation, MountFlags::SLAVE | MountFlags::REC);
+    assert_eq!(e.flags.to_string(), "MS_RDONLY|MS_BIND|MS_REC");
+    assert_eq!(e.flags.bits(), 1 | 4096 | 16384);
+    assert_eq!(MountFlags::empty().to_string(), "0x0");
+    assert_eq!(MountFlags::from_bits(1 << 30).to_string(), "0x40000000");
+}
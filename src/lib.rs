@@ -1,80 +1,147 @@
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+extern crate libc;
+
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::str::FromStr;
+
+mod diagnostic;
+mod diff;
+mod document;
+mod drift;
+mod error;
+mod flags;
+mod lint;
+mod mountinfo;
+#[cfg(feature = "mount")]
+mod mount;
+mod options;
+mod plan;
+mod probe;
+mod resolve;
+mod save;
+#[cfg(feature = "serde")]
+mod serialize;
+mod table;
+mod textdiff;
+mod verify;
+
+pub use diagnostic::{
+    open_fstab_lenient, parse_fstab_lenient, read_fstab_lenient, Diagnostic, ParseReport, Severity,
+};
+pub use diff::{diff_documents, diff_tables, EntryDiff, FieldChange, TableDiff};
+pub use document::{EntryLine, FstabDocument, Line};
+pub use drift::{detect_drift, drift, Drift};
+pub use error::{Error, ErrorType};
+pub use flags::{EffectiveOptions, MountFlags};
+pub use lint::{lint, Lint, Linter, Rule};
+#[cfg(feature = "mount")]
+pub use mount::{mount_all, MountError, MountOutcome, MountReport, Mounter};
+pub use mountinfo::{
+    open_mountinfo, parse_mountinfo, read_mountinfo, MountInfo, MountTree, OptionalField,
+};
+pub use options::{MountOption, MountOptions, OptionKind};
+pub use plan::{plan, MountCycle, MountPlan, Shadowed};
+pub use probe::{probe, probe_reader, Superblock};
+pub use resolve::{ResolveError, Resolver};
+pub use save::{Backup, SaveOptions, Saved};
+pub use table::{match_fstype, match_options, FstabTable};
+pub use verify::{check, verify, Environment, Problem, ProblemKind};
 
 /// Default Path for `fstab`
-const FSTAB_PATH: &'static str = "/etc/fstab";
+const FSTAB_PATH: &str = "/etc/fstab";
+/// Default Path for the table of mounted filesystems
+const MOUNTS_PATH: &str = "/proc/self/mounts";
+/// Default Path for `mtab`
+const MTAB_PATH: &str = "/etc/mtab";
 
 type Result<T> = std::result::Result<T, Error>;
 
-/// Type of errors
-#[derive(Debug, Clone)]
-pub enum ErrorType {
-///   `fstab` file does not exist at the given path
-    FstabNotExist(String),
-///   The numbers in `dump` and `fsck` fields are incorrect
-    NumParseError(String),
-///   Required fields(UUID, device type, etc.) do not exist
-    FieldNotExist(usize),
-///   Extra failds after `fsck`
-    TooManyFields(String),
+/// Types of device name
+///
+/// Devices are named either by a tag or by a path:
+///
+/// * UUID (UUID=F1C1-3AC0)
+/// * LABEL (LABEL=MyDisk)
+/// * PARTUUID (PARTUUID=8c2a7f1e-02)
+/// * PARTLABEL (PARTLABEL=EFI)
+/// * ID (ID=ata-ST1000DM003_Z1D5K1ZE-part1)
+/// * Mount Point (/dev/sda)
+/// * NFS export (server:/export)
+/// * CIFS share (//server/share)
+/// * Pseudo filesystem source (tmpfs, proc, none)
+///
+/// Tag names are matched case-insensitively, and the value may be quoted
+/// (`LABEL="My Disk"`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Device {
+    Uuid(String),
+    Label(String),
+    /// An absolute path, usually a block device or the source of a bind mount
+    MountPoint(String),
+    PartUuid(String),
+    PartLabel(String),
+    Id(String),
+    /// A `host:/path` source, as used by NFS and other network filesystems
+    ///
+    /// IPv6 hosts are written in brackets (`[fe80::1]:/export`) and stored without.
+    Nfs { host: String, export: String },
+    /// A `//host/share` source, as used by CIFS/SMB
+    Cifs { host: String, share: String },
+    /// A name that is not a path, such as `tmpfs`, `proc` or `none`
+    Pseudo(String),
 }
 
-#[derive(Debug, Clone)]
-pub struct Error {
-    reason: ErrorType,
-}
+impl Device {
+    /// Returns `true` if the device is given by a tag (`UUID=`, `LABEL=`, ...)
+    pub fn is_tag(&self) -> bool {
+        matches!(
+            *self,
+            Device::Uuid(_)
+                | Device::Label(_)
+                | Device::PartUuid(_)
+                | Device::PartLabel(_)
+                | Device::Id(_)
+        )
+    }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+    /// Returns `true` for network sources (`host:/path` and `//host/share`)
+    pub fn is_network(&self) -> bool {
+        self.host().is_some()
     }
-}
 
-impl From<std::io::Error> for Error {
-    fn from(e: std::io::Error) -> Self {
-        use std::error::Error;
-        self::Error {
-            reason: ErrorType::FstabNotExist(e.description().to_owned()),
-        }
+    /// Returns `true` for sources that are not a path, tag or network location
+    pub fn is_pseudo(&self) -> bool {
+        matches!(*self, Device::Pseudo(_))
     }
-}
 
-impl From<std::num::ParseIntError> for Error {
-    fn from(e: std::num::ParseIntError) -> Self {
-        use std::error::Error;
-        self::Error {
-            reason: ErrorType::NumParseError(e.description().to_owned()),
+    /// The host of a network source
+    pub fn host(&self) -> Option<&str> {
+        match *self {
+            Device::Nfs { ref host, .. } | Device::Cifs { ref host, .. } => Some(host),
+            _ => None,
         }
     }
-}
 
-impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        match self.reason {
-            ErrorType::FstabNotExist(_) => "can not open fstab",
-            _ => "unkonwn error",
+    /// The path of a `MountPoint` source
+    pub fn path(&self) -> Option<&str> {
+        match *self {
+            Device::MountPoint(ref p) => Some(p),
+            _ => None,
         }
     }
 }
 
-/// Types of device name
-///
-/// Devices have 3 possible types of names:
-///
-/// * UUID (F1C1-3AC0)
-/// * LABEL (MyDisk)
-/// * Mount Point (/dev/sda)
-#[derive(Debug, Clone)]
-pub enum Device {
-    Uuid(String),
-    Label(String),
-    MountPoint(String),
-    PartUuid(String),
-    PartLabel(String),
-}
-
 /// Types for storing an item of fstab
-#[derive(Debug, Clone)]
+///
+/// With the `serde` feature, an entry is serialized as a map with the field
+/// names below; `device` is written in `fs_spec` form and `options` as a
+/// list. `options`, `dump` and `fsck` may be left out when deserializing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Fstab {
     /// fs_spec, the block special device or remote filesystem to be mounted
     pub device: Device,
@@ -83,88 +150,683 @@ pub struct Fstab {
     /// fs_vfstype, the type of the filesystem
     pub device_type: String,
     /// fs_mntops, mount options
-    pub options: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub options: MountOptions,
     /// fs_freq, need to be dumped or not
+    #[cfg_attr(feature = "serde", serde(default))]
     pub dump: bool,
     /// fs_passno, filesystem checks are done at boot time or not
+    #[cfg_attr(feature = "serde", serde(default))]
     pub fsck: usize,
 }
 
+impl fmt::Display for Device {
+    /// Writes the device in `fs_spec` form, e.g. `UUID=F1C1-3AC0` or `/dev/sda1`
+    ///
+    /// A tag value that starts with a quote is quoted, so that parsing the
+    /// output gives back the same value.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (tag, value) = match *self {
+            Device::Uuid(ref s) => ("UUID", s),
+            Device::Label(ref s) => ("LABEL", s),
+            Device::PartUuid(ref s) => ("PARTUUID", s),
+            Device::PartLabel(ref s) => ("PARTLABEL", s),
+            Device::Id(ref s) => ("ID", s),
+            Device::MountPoint(ref s) | Device::Pseudo(ref s) => return f.write_str(s),
+            Device::Nfs {
+                ref host,
+                ref export,
+            } => {
+                return if host.contains(':') {
+                    write!(f, "[{}]:{}", host, export)
+                } else {
+                    write!(f, "{}:{}", host, export)
+                };
+            }
+            Device::Cifs {
+                ref host,
+                ref share,
+            } => return write!(f, "//{}/{}", host, share),
+        };
+        if value.starts_with('"') || value.starts_with('\'') {
+            write!(f, "{}=\"{}\"", tag, value)
+        } else {
+            write!(f, "{}={}", tag, value)
+        }
+    }
+}
+
+impl FromStr for Device {
+    type Err = std::convert::Infallible;
+
+    /// Parse a device in `fs_spec` form; anything that is not a tag is
+    /// classified as a path, a network source or a pseudo source
+    fn from_str(s: &str) -> std::result::Result<Device, std::convert::Infallible> {
+        Ok(parse_device(s))
+    }
+}
+
+impl fmt::Display for Fstab {
+    /// Writes the entry as a single fstab line, with fields separated by tabs
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format_fields(self).join("\t"))
+    }
+}
+
+impl FromStr for Fstab {
+    type Err = Error;
+
+    /// Parse a single fstab line
+    ///
+    /// Comments and empty lines are rejected, as they carry no entry.
+    fn from_str(s: &str) -> Result<Fstab> {
+        if is_ignored_line(s) {
+            return Err(Error::new(ErrorType::FieldNotExist(0)).with_text(s));
+        }
+        parse_line(s)
+    }
+}
+
 fn parse_device(name: &str) -> Device {
-    if name.starts_with("UUID=") {
-        Device::Uuid(name.split_at(5).1.to_owned())
-    } else if name.starts_with("LABEL=") {
-        Device::Label(name.split_at(6).1.to_owned())
-    } else if name.starts_with("PARTUUID=") {
-        Device::PartUuid(name.split_at(5).1.to_owned())
-    } else if name.starts_with("PARTLABEL=") {
-        Device::PartLabel(name.split_at(6).1.to_owned())
+    let (tag, value) = match name.find('=') {
+        Some(i) => (name[..i].to_ascii_uppercase(), unquote(&name[i + 1..])),
+        None => return parse_source(name),
+    };
+    match tag.as_str() {
+        "UUID" => Device::Uuid(value.to_owned()),
+        "LABEL" => Device::Label(value.to_owned()),
+        "PARTUUID" => Device::PartUuid(value.to_owned()),
+        "PARTLABEL" => Device::PartLabel(value.to_owned()),
+        "ID" => Device::Id(value.to_owned()),
+        _ => parse_source(name),
+    }
+}
+
+/// Classify a source that is not a tag
+fn parse_source(name: &str) -> Device {
+    if let Some(rest) = name.strip_prefix("//") {
+        let (host, share) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => (rest, ""),
+        };
+        if !host.is_empty() {
+            return Device::Cifs {
+                host: host.to_owned(),
+                share: share.to_owned(),
+            };
+        }
+    }
+    if name.starts_with('/') {
+        return Device::MountPoint(name.to_owned());
+    }
+    let split = if name.starts_with('[') {
+        name.find("]:").map(|i| (&name[1..i], &name[i + 2..]))
     } else {
-        Device::MountPoint(name.to_owned())
+        name.find(':').map(|i| (&name[..i], &name[i + 1..]))
+    };
+    match split {
+        Some((host, export)) if !host.is_empty() && !host.contains('/') && export.starts_with('/') => {
+            Device::Nfs {
+                host: host.to_owned(),
+                export: export.to_owned(),
+            }
+        }
+        _ => Device::Pseudo(name.to_owned()),
     }
 }
 
+/// Strip one pair of matching double or single quotes around `s`
+fn unquote(s: &str) -> &str {
+    for q in &['"', '\''] {
+        if s.len() >= 2 && s.starts_with(*q) && s.ends_with(*q) {
+            return &s[1..s.len() - 1];
+        }
+    }
+    s
+}
+
+/// Split a line into whitespace separated fields, keeping the byte offset of each field
+fn split_fields(line: &str) -> Vec<(usize, &str)> {
+    let mut fields = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                fields.push((s, &line[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        fields.push((s, &line[s..]));
+    }
+    fields
+}
+
+/// Returns `true` if the line carries no entry (empty, whitespace only or a comment)
+fn is_ignored_line(line: &str) -> bool {
+    let l = line.trim();
+    l.starts_with('#') || l.is_empty()
+}
+
+/// Parse a single non-comment line into a `Fstab`
+///
+/// Errors carry the line and the span of the offending field.
+fn parse_line(line: &str) -> Result<Fstab> {
+    let fields = split_fields(line);
+    let field = |i: usize| {
+        fields.get(i).map(|&(_, f)| f).ok_or_else(|| {
+            let end = line.trim_end().len();
+            Error::new(ErrorType::FieldNotExist(i)).with_span(end..end)
+        })
+    };
+    let number = |i: usize| match fields.get(i) {
+        Some(&(start, f)) => f
+            .parse::<usize>()
+            .map(Some)
+            .map_err(|e| Error::from(e).with_span(start..start + f.len())),
+        None => Ok(None),
+    };
+    let parse = || {
+        if fields.len() > 6 {
+            let start = fields[6].0;
+            let end = line.trim_end().len();
+            return Err(Error::new(ErrorType::TooManyFields(line[start..end].to_owned()))
+                .with_span(start..end));
+        }
+        Ok(Fstab {
+            device: parse_device(&unescape(field(0)?)),
+            dir: unescape(field(1)?),
+            device_type: field(2)?.to_owned(),
+            options: MountOptions::from(field(3)?),
+            dump: number(4)?.map_or(false, |x| x > 0),
+            fsck: number(5)?.unwrap_or(0),
+        })
+    };
+    parse().map_err(|e| e.with_text(line))
+}
+
+/// Octal-escape the characters that would otherwise split a field
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            ' ' => out.push_str("\\040"),
+            '\t' => out.push_str("\\011"),
+            '\n' => out.push_str("\\012"),
+            '\\' => out.push_str("\\134"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Decode octal escapes (`\040`, `\011`, `\012`, `\134`, ...) in a field
+///
+/// A backslash that is not followed by three octal digits is kept as it is,
+/// and so is the whole field when the decoded bytes are not valid UTF-8.
+fn unescape(s: &str) -> String {
+    if !s.contains('\\') {
+        return s.to_owned();
+    }
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|d| {
+            if bytes[i] == b'\\' && d[0] <= b'3' && d.iter().all(|b| (b'0'..=b'7').contains(b)) {
+                Some(d.iter().fold(0u8, |acc, b| acc * 8 + (b - b'0')))
+            } else {
+                None
+            }
+        });
+        match octal {
+            Some(b) => {
+                out.push(b);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).unwrap_or_else(|_| s.to_owned())
+}
+
+/// The mount point with repeated and trailing slashes and `.` components removed
+///
+/// Anything that is not an absolute path, like `none` for swap, is returned as is.
+fn normalize_dir(dir: &str) -> String {
+    if !dir.starts_with('/') {
+        return dir.to_owned();
+    }
+    let parts = dir
+        .split('/')
+        .filter(|p| !p.is_empty() && *p != ".")
+        .collect::<Vec<_>>();
+    format!("/{}", parts.join("/"))
+}
+
+/// Render the fields of an entry in fstab column order
+///
+/// `dump` and `fsck` are always emitted, and empty options are written as `defaults`.
+fn format_fields(fstab: &Fstab) -> Vec<String> {
+    vec![
+        escape(&fstab.device.to_string()),
+        escape(&fstab.dir),
+        fstab.device_type.clone(),
+        if fstab.options.is_empty() {
+            "defaults".to_owned()
+        } else {
+            fstab.options.to_string()
+        },
+        if fstab.dump { "1" } else { "0" }.to_owned(),
+        fstab.fsck.to_string(),
+    ]
+}
+
+/// Render a list of `Fstab` as the content of a fstab file
+///
+/// Each entry is written on its own line, with the columns aligned.
+pub fn fstab_to_string(list: &[Fstab]) -> String {
+    let rows = list.iter().map(format_fields).collect::<Vec<_>>();
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (w, field) in widths.iter_mut().zip(row) {
+            *w = std::cmp::max(*w, field.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in &rows {
+        let mut line = String::new();
+        for (i, field) in row.iter().enumerate() {
+            if i > 0 {
+                line.push(' ');
+            }
+            line.push_str(field);
+            if i + 1 < row.len() {
+                line.push_str(&" ".repeat(widths[i] - field.chars().count()));
+            }
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// Write a list of `Fstab` to `writer` in fstab format
+pub fn write_fstab<W: Write>(mut writer: W, list: &[Fstab]) -> Result<()> {
+    writer.write_all(fstab_to_string(list).as_bytes())?;
+    Ok(())
+}
+
 /// Open a fstab file and read it into a list of `Fstab`
 /// When `path` is set to `None`, this function will use the default path.
 pub fn open_fstab(path: Option<&str>) -> Result<Vec<Fstab>> {
-    let fstab_handle = File::open(match path {
-        Some(p) => p,
-        _ => FSTAB_PATH,
-    })?;
+    open_table(path.unwrap_or(FSTAB_PATH))
+}
 
-    let reader = BufReader::new(fstab_handle);
+/// Open the table of currently mounted filesystems and read it into a list of `Fstab`
+/// When `path` is set to `None`, this function will use `/proc/self/mounts`.
+///
+/// `/proc/mounts` uses the same format as fstab, with `dump` and `fsck`
+/// always set to 0.
+pub fn open_mounts(path: Option<&str>) -> Result<Vec<Fstab>> {
+    open_table(path.unwrap_or(MOUNTS_PATH))
+}
+
+/// Open a mtab file and read it into a list of `Fstab`
+/// When `path` is set to `None`, this function will use `/etc/mtab`.
+///
+/// On most systems `/etc/mtab` is a symlink to `/proc/self/mounts`.
+pub fn open_mtab(path: Option<&str>) -> Result<Vec<Fstab>> {
+    open_table(path.unwrap_or(MTAB_PATH))
+}
+
+/// Read a file in fstab format, adding `path` to errors
+fn open_table(path: &str) -> Result<Vec<Fstab>> {
+    File::open(path)
+        .map_err(Error::from)
+        .and_then(read_fstab)
+        .map_err(|e| e.with_path(path))
+}
+
+/// Parse the content of a fstab file into a list of `Fstab`
+pub fn parse_fstab(content: &str) -> Result<Vec<Fstab>> {
+    read_fstab(content.as_bytes())
+}
+
+/// Read fstab content from any reader into a list of `Fstab`
+pub fn read_fstab<R: Read>(reader: R) -> Result<Vec<Fstab>> {
+    let mut reader = BufReader::new(reader);
 
     let mut fstab_item_list = Vec::new();
 
-    for l in reader.lines() {
-        if let Ok(l) = l {
-            let l = l.trim();
-            if l.starts_with("#") || l.len() == 0 {
-                continue;
-            }
-            let mut tabs = l.split_whitespace();
-            fstab_item_list.push(Fstab {
-                device: parse_device(tabs.next().ok_or(Error {
-                    reason: ErrorType::FieldNotExist(0),
-                })?),
-                dir: tabs.next()
-                    .ok_or(Error {
-                        reason: ErrorType::FieldNotExist(1),
-                    })?
-                    .to_owned(),
-                device_type: tabs.next()
-                    .ok_or(Error {
-                        reason: ErrorType::FieldNotExist(2),
-                    })?
-                    .to_owned(),
-                options: tabs.next()
-                    .ok_or(Error {
-                        reason: ErrorType::FieldNotExist(3),
-                    })?
-                    .split(",")
-                    .map(|x| x.to_owned())
-                    .collect::<Vec<_>>(),
-                dump: match tabs.next() {
-                    Some(x) => x.parse::<usize>()
-                        .map(|x| if x > 0 { true } else { false })?,
-                    _ => false,
-                },
-                fsck: match tabs.next() {
-                    Some(x) => x.parse::<usize>()?,
-                    _ => 0,
-                },
-            });
-            if tabs.next().is_some() {
-                return Err(Error { reason: ErrorType::TooManyFields(l.to_owned())});
-            }
+    let mut n = 0;
+    while let Some(l) = read_line(&mut reader, &mut n)? {
+        if is_ignored_line(&l) {
+            continue;
         }
+        fstab_item_list.push(parse_line(&l).map_err(|e| e.with_line(n))?);
     }
     Ok(fstab_item_list)
 }
 
+/// Read the next line, without its line terminator, and count it in `n`
+///
+/// Returns `None` at the end of input. Lines that are not valid UTF-8
+/// are reported as `ErrorType::InvalidUtf8`.
+fn read_line<R: BufRead>(reader: &mut R, n: &mut usize) -> Result<Option<String>> {
+    let mut buf = Vec::new();
+    if reader.read_until(b'\n', &mut buf)? == 0 {
+        return Ok(None);
+    }
+    *n += 1;
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+    }
+    String::from_utf8(buf)
+        .map(Some)
+        .map_err(|e| invalid_utf8(e.as_bytes(), *n))
+}
+
+/// Build the error for line number `n`, whose content `line` is not valid UTF-8
+///
+/// The text of the error is the line with invalid bytes replaced by U+FFFD,
+/// and the span points at the first replacement character.
+fn invalid_utf8(line: &[u8], n: usize) -> Error {
+    let start = match std::str::from_utf8(line) {
+        Ok(_) => line.len(),
+        Err(e) => e.valid_up_to(),
+    };
+    Error::new(ErrorType::InvalidUtf8)
+        .with_line(n)
+        .with_span(start..start + '\u{fffd}'.len_utf8())
+        .with_text(&String::from_utf8_lossy(line))
+}
+
+/// Create an empty directory for a test, named after the test and the process
+#[cfg(test)]
+fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("fstab-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
 #[test]
 fn read_default_fstab() {
     let fstab = open_fstab(None);
     println!("{:?}", fstab);
     assert!(fstab.is_ok());
 }
+
+#[test]
+fn device_tags() {
+    let cases = vec![
+        ("UUID=F1C1-3AC0", Device::Uuid("F1C1-3AC0".to_owned())),
+        ("LABEL=MyDisk", Device::Label("MyDisk".to_owned())),
+        ("PARTUUID=8c2a7f1e-02", Device::PartUuid("8c2a7f1e-02".to_owned())),
+        ("PARTLABEL=EFI", Device::PartLabel("EFI".to_owned())),
+        ("ID=ata-disk-part1", Device::Id("ata-disk-part1".to_owned())),
+        ("/dev/sda1", Device::MountPoint("/dev/sda1".to_owned())),
+        ("tmpfs", Device::Pseudo("tmpfs".to_owned())),
+        ("LABEL=a=b", Device::Label("a=b".to_owned())),
+        ("UUID=", Device::Uuid(String::new())),
+        ("LABEL=\"\"x\"\"", Device::Label("\"x\"".to_owned())),
+    ];
+    for (s, device) in cases {
+        assert_eq!(s.parse::<Device>().unwrap(), device);
+        assert_eq!(device.to_string(), s);
+    }
+
+    assert_eq!(
+        "uuid=f1c1-3ac0".parse::<Device>().unwrap(),
+        Device::Uuid("f1c1-3ac0".to_owned())
+    );
+    assert_eq!(
+        "PartLabel=\"EFI System\"".parse::<Device>().unwrap(),
+        Device::PartLabel("EFI System".to_owned())
+    );
+    assert_eq!(
+        "label='x'".parse::<Device>().unwrap(),
+        Device::Label("x".to_owned())
+    );
+    assert_eq!(
+        "LABEL=\"x".parse::<Device>().unwrap(),
+        Device::Label("\"x".to_owned())
+    );
+    assert_eq!(
+        "/dev/disk=1".parse::<Device>().unwrap(),
+        Device::MountPoint("/dev/disk=1".to_owned())
+    );
+    assert_eq!(
+        "SERVER:/a=b".parse::<Device>().unwrap(),
+        Device::Nfs {
+            host: "SERVER".to_owned(),
+            export: "/a=b".to_owned(),
+        }
+    );
+
+    let fstab = "LABEL=\"My\\040Disk\" /mnt ext4 defaults".parse::<Fstab>().unwrap();
+    assert_eq!(fstab.device, Device::Label("My Disk".to_owned()));
+    assert_eq!(
+        fstab.to_string().parse::<Fstab>().unwrap().device,
+        fstab.device
+    );
+}
+
+#[test]
+fn device_sources() {
+    let cases = vec![
+        ("/dev/sda1", Device::MountPoint("/dev/sda1".to_owned())),
+        ("/srv/data", Device::MountPoint("/srv/data".to_owned())),
+        (
+            "nas.local:/export/home",
+            Device::Nfs {
+                host: "nas.local".to_owned(),
+                export: "/export/home".to_owned(),
+            },
+        ),
+        (
+            "[fe80::1]:/export",
+            Device::Nfs {
+                host: "fe80::1".to_owned(),
+                export: "/export".to_owned(),
+            },
+        ),
+        (
+            "//fileserver/public/docs",
+            Device::Cifs {
+                host: "fileserver".to_owned(),
+                share: "public/docs".to_owned(),
+            },
+        ),
+        (
+            "//fileserver/",
+            Device::Cifs {
+                host: "fileserver".to_owned(),
+                share: String::new(),
+            },
+        ),
+        ("none", Device::Pseudo("none".to_owned())),
+        ("proc", Device::Pseudo("proc".to_owned())),
+        ("server:volume", Device::Pseudo("server:volume".to_owned())),
+        ("host/x:/y", Device::Pseudo("host/x:/y".to_owned())),
+    ];
+    for (s, device) in cases {
+        assert_eq!(s.parse::<Device>().unwrap(), device);
+        assert_eq!(device.to_string(), s);
+    }
+
+    let nfs = "nas.local:/export/home".parse::<Device>().unwrap();
+    assert!(nfs.is_network());
+    assert_eq!(nfs.host(), Some("nas.local"));
+    assert!(!nfs.is_tag());
+    let cifs = "//fileserver/public".parse::<Device>().unwrap();
+    assert_eq!(cifs.host(), Some("fileserver"));
+    assert!("tmpfs".parse::<Device>().unwrap().is_pseudo());
+    assert!("UUID=x".parse::<Device>().unwrap().is_tag());
+    assert_eq!("/dev/sda1".parse::<Device>().unwrap().path(), Some("/dev/sda1"));
+    assert_eq!(
+        "//".parse::<Device>().unwrap(),
+        Device::MountPoint("//".to_owned())
+    );
+}
+
+#[test]
+fn read_mounts() {
+    let mounts = open_mounts(None).unwrap();
+    assert!(!mounts.is_empty());
+    assert!(mounts.iter().all(|m| !m.dump && m.fsck == 0));
+    assert!(open_mtab(Some(MOUNTS_PATH)).is_ok());
+
+    let content = "/dev/sda1 / ext4 rw,relatime,errors=remount-ro 0 0\n\
+                   /dev/sdb1 /media/usb\\040stick vfat rw,nosuid,nodev,uid=1000 0 0\n";
+    let mounts = parse_fstab(content).unwrap();
+    assert_eq!(mounts[1].dir, "/media/usb stick");
+    assert!(mounts[1].options.effective().flags.contains(MountFlags::NOSUID));
+}
+
+#[test]
+fn parse_fstab_content() {
+    let content = "# comment\n\nUUID=F1C1-3AC0 /boot/efi vfat umask=0077 0 2\nproc /proc proc defaults\n";
+    let list = parse_fstab(content).unwrap();
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[0].device, Device::Uuid("F1C1-3AC0".to_owned()));
+    assert_eq!(list[1].dir, "/proc");
+    assert_eq!(list[1].fsck, 0);
+
+    let list = read_fstab(std::io::Cursor::new(content)).unwrap();
+    assert_eq!(list.len(), 2);
+
+    let fstab = "/dev/sda1 / ext4 rw,relatime 1 1".parse::<Fstab>().unwrap();
+    assert_eq!(fstab.options, MountOptions::from("rw,relatime"));
+    assert!(fstab.dump);
+    assert!("# /dev/sda1 / ext4 defaults".parse::<Fstab>().is_err());
+    assert!("/dev/sda1 /".parse::<Fstab>().is_err());
+}
+
+#[test]
+fn parse_error_location() {
+    let content = "proc /proc proc defaults\n\n/dev/sda1 /   ext4 defaults 1 x\n";
+    let e = parse_fstab(content).unwrap_err();
+    assert_eq!(e.line(), Some(3));
+    assert_eq!(e.span(), Some(30..31));
+    assert_eq!(e.columns(), Some(31..32));
+    assert_eq!(e.text(), Some("/dev/sda1 /   ext4 defaults 1 x"));
+    assert_eq!(e.to_string(), "3:31: invalid number: invalid digit found in string");
+    assert_eq!(
+        format!("{:#}", e),
+        concat!(
+            "3:31: invalid number: invalid digit found in string\n",
+            "    /dev/sda1 /   ext4 defaults 1 x\n",
+            "                                  ^",
+        )
+    );
+
+    let e = parse_fstab("/dev/sda1\t/ \n").unwrap_err();
+    assert_eq!(e.to_string(), "1:12: missing field 3 (fs_vfstype)");
+    let e = parse_fstab("a b c d 0 0 e f").unwrap_err();
+    assert_eq!(e.to_string(), "1:13: too many fields: e f");
+    assert_eq!(e.columns(), Some(13..16));
+
+    let e = open_fstab(Some("/nonexistent/fstab")).unwrap_err();
+    assert!(e.to_string().starts_with("/nonexistent/fstab: can not open fstab: "));
+}
+
+#[test]
+fn read_errors() {
+    use std::error::Error as StdError;
+
+    let e = read_fstab(&b"proc /proc proc defaults\n/dev/sda1 /mnt/\xff ext4 defaults\n"[..]).unwrap_err();
+    match *e.reason() {
+        ErrorType::InvalidUtf8 => {}
+        ref r => panic!("unexpected error {:?}", r),
+    }
+    assert_eq!(e.line(), Some(2));
+    assert_eq!(e.columns(), Some(16..17));
+    assert_eq!(e.text(), Some("/dev/sda1 /mnt/\u{fffd} ext4 defaults"));
+
+    let e = open_fstab(Some("/nonexistent/fstab")).unwrap_err();
+    match *e.reason() {
+        ErrorType::FstabNotExist(_) => {}
+        ref r => panic!("unexpected error {:?}", r),
+    }
+    let source = e.source().unwrap().downcast_ref::<std::io::Error>().unwrap();
+    assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+
+    let e = Error::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+    match *e.reason() {
+        ErrorType::PermissionDenied(_) => {}
+        ref r => panic!("unexpected error {:?}", r),
+    }
+    let e = open_fstab(Some("/")).unwrap_err();
+    match *e.reason() {
+        ErrorType::Io(_) => {}
+        ref r => panic!("unexpected error {:?}", r),
+    }
+    assert!(e.source().is_some());
+}
+
+#[test]
+fn write_fstab_lines() {
+    let list = vec![
+        Fstab {
+            device: Device::Uuid("F1C1-3AC0".to_owned()),
+            dir: "/boot/efi".to_owned(),
+            device_type: "vfat".to_owned(),
+            options: MountOptions::from("umask=0077"),
+            dump: false,
+            fsck: 2,
+        },
+        Fstab {
+            device: Device::Label("My Disk".to_owned()),
+            dir: "/mnt/my disk\tbackup".to_owned(),
+            device_type: "ext4".to_owned(),
+            options: MountOptions::from("noatime,nofail"),
+            dump: true,
+            fsck: 0,
+        },
+        Fstab {
+            device: Device::Pseudo("tmpfs".to_owned()),
+            dir: "/tmp".to_owned(),
+            device_type: "tmpfs".to_owned(),
+            options: MountOptions::new(),
+            dump: false,
+            fsck: 0,
+        },
+    ];
+    assert_eq!(list[0].to_string(), "UUID=F1C1-3AC0\t/boot/efi\tvfat\tumask=0077\t0\t2");
+    assert_eq!(
+        fstab_to_string(&list),
+        "UUID=F1C1-3AC0   /boot/efi                 vfat  umask=0077     0 2\n\
+         LABEL=My\\040Disk /mnt/my\\040disk\\011backup ext4  noatime,nofail 1 0\n\
+         tmpfs            /tmp                      tmpfs defaults       0 0\n"
+    );
+    let mut out = Vec::new();
+    write_fstab(&mut out, &list[..1]).unwrap();
+    assert_eq!(out, b"UUID=F1C1-3AC0 /boot/efi vfat umask=0077 0 2\n");
+    let parsed = parse_line(&list[1].to_string()).unwrap();
+    assert_eq!(parsed, list[1]);
+}
+
+#[test]
+fn octal_escapes() {
+    let fstab = parse_line("LABEL=My\\040Disk /mnt/My\\040Disk\\011x\\134y ntfs defaults").unwrap();
+    assert_eq!(fstab.device, Device::Label("My Disk".to_owned()));
+    assert_eq!(fstab.dir, "/mnt/My Disk\tx\\y");
+    assert_eq!(
+        fstab.to_string(),
+        "LABEL=My\\040Disk\t/mnt/My\\040Disk\\011x\\134y\tntfs\tdefaults\t0\t0"
+    );
+    assert_eq!(unescape("a\\b\\04\\400\\0401"), "a\\b\\04\\400 1");
+    assert_eq!(unescape("\\303\\251t\\303\\251"), "été");
+    assert_eq!(unescape("\\377"), "\\377");
+}
@@ -0,0 +1,694 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use options::negation;
+use verify::can_fsck;
+use {normalize_dir, Device, Fstab, OptionKind, Severity};
+
+/// Types served over the network, whatever the device looks like
+const NETWORK_TYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "ceph",
+    "glusterfs",
+    "9p",
+    "fuse.sshfs",
+    "davfs",
+];
+
+/// Kernel device names that depend on probe order, as prefixes of `/dev/<name>`
+const UNSTABLE_NAMES: &[&str] = &["sd", "hd", "vd", "xvd", "nvme", "mmcblk"];
+
+const EXT_OPTIONS: &[&str] = &[
+    "acl",
+    "user_xattr",
+    "barrier",
+    "commit",
+    "data",
+    "data_err",
+    "errors",
+    "journal_checksum",
+    "journal_async_commit",
+    "journal_path",
+    "journal_dev",
+    "journal_ioprio",
+    "load",
+    "recovery",
+    "orlov",
+    "oldalloc",
+    "grpid",
+    "bsdgroups",
+    "sysvgroups",
+    "resgid",
+    "resuid",
+    "sb",
+    "quota",
+    "usrquota",
+    "grpquota",
+    "prjquota",
+    "usrjquota",
+    "grpjquota",
+    "jqfmt",
+    "bh",
+    "stripe",
+    "delalloc",
+    "max_batch_time",
+    "min_batch_time",
+    "auto_da_alloc",
+    "discard",
+    "init_itable",
+    "dioread_lock",
+    "dioread_nolock",
+    "i_version",
+    "inode_readahead_blks",
+    "block_validity",
+    "debug",
+    "minixdf",
+    "bsddf",
+    "uid32",
+    "check",
+    "dax",
+];
+
+const XFS_OPTIONS: &[&str] = &[
+    "allocsize",
+    "attr2",
+    "dax",
+    "discard",
+    "grpid",
+    "bsdgroups",
+    "sysvgroups",
+    "filestreams",
+    "ikeep",
+    "inode32",
+    "inode64",
+    "largeio",
+    "logbufs",
+    "logbsize",
+    "logdev",
+    "align",
+    "recovery",
+    "uuid",
+    "quota",
+    "uquota",
+    "usrquota",
+    "uqnoenforce",
+    "qnoenforce",
+    "pquota",
+    "prjquota",
+    "pqnoenforce",
+    "gquota",
+    "grpquota",
+    "gqnoenforce",
+    "rtdev",
+    "sunit",
+    "swidth",
+    "swalloc",
+    "wsync",
+];
+
+const BTRFS_OPTIONS: &[&str] = &[
+    "acl",
+    "autodefrag",
+    "barrier",
+    "check_int",
+    "check_int_data",
+    "check_int_print_mask",
+    "clear_cache",
+    "commit",
+    "compress",
+    "compress-force",
+    "datacow",
+    "datasum",
+    "degraded",
+    "device",
+    "discard",
+    "enospc_debug",
+    "fatal_errors",
+    "flushoncommit",
+    "fragment",
+    "inode_cache",
+    "max_inline",
+    "metadata_ratio",
+    "recovery",
+    "rescan_uuid_tree",
+    "rescue",
+    "skip_balance",
+    "space_cache",
+    "ssd",
+    "ssd_spread",
+    "subvol",
+    "subvolid",
+    "thread_pool",
+    "treelog",
+    "usebackuproot",
+    "user_subvol_rm_allowed",
+];
+
+const VFAT_OPTIONS: &[&str] = &[
+    "blocksize",
+    "uid",
+    "gid",
+    "umask",
+    "dmask",
+    "fmask",
+    "allow_utime",
+    "check",
+    "codepage",
+    "conv",
+    "debug",
+    "discard",
+    "dos1xfloppy",
+    "errors",
+    "fat",
+    "iocharset",
+    "nfs",
+    "tz",
+    "time_offset",
+    "quiet",
+    "rodir",
+    "showexec",
+    "sys_immutable",
+    "flush",
+    "usefree",
+    "dots",
+    "dotsOK",
+    "shortname",
+    "utf8",
+    "uni_xlate",
+    "posix",
+    "numtail",
+];
+
+const TMPFS_OPTIONS: &[&str] = &[
+    "size",
+    "nr_blocks",
+    "nr_inodes",
+    "mode",
+    "uid",
+    "gid",
+    "mpol",
+    "huge",
+    "inode32",
+    "inode64",
+    "swap",
+    "quota",
+    "usrquota",
+    "grpquota",
+];
+
+const NFS_OPTIONS: &[&str] = &[
+    "soft",
+    "hard",
+    "softerr",
+    "timeo",
+    "retrans",
+    "rsize",
+    "wsize",
+    "ac",
+    "acregmin",
+    "acregmax",
+    "acdirmin",
+    "acdirmax",
+    "actimeo",
+    "bg",
+    "fg",
+    "nconnect",
+    "max_connect",
+    "rdirplus",
+    "retry",
+    "sec",
+    "sharecache",
+    "resvport",
+    "lookupcache",
+    "fsc",
+    "sloppy",
+    "proto",
+    "udp",
+    "tcp",
+    "rdma",
+    "port",
+    "mountport",
+    "mountproto",
+    "mounthost",
+    "mountvers",
+    "namlen",
+    "nfsvers",
+    "vers",
+    "lock",
+    "cto",
+    "intr",
+    "acl",
+    "local_lock",
+    "minorversion",
+    "clientaddr",
+    "migration",
+    "addr",
+];
+
+const CIFS_OPTIONS: &[&str] = &[
+    "username",
+    "user",
+    "password",
+    "pass",
+    "credentials",
+    "domain",
+    "dom",
+    "workgroup",
+    "uid",
+    "gid",
+    "forceuid",
+    "forcegid",
+    "port",
+    "netbiosname",
+    "servern",
+    "file_mode",
+    "dir_mode",
+    "ip",
+    "addr",
+    "vers",
+    "sec",
+    "cache",
+    "unix",
+    "serverino",
+    "mapchars",
+    "mapposix",
+    "iocharset",
+    "rsize",
+    "wsize",
+    "actimeo",
+    "closetimeo",
+    "hard",
+    "soft",
+    "brl",
+    "seal",
+    "perm",
+    "sfu",
+    "multiuser",
+    "cruid",
+    "guest",
+    "echo_interval",
+    "snapshot",
+    "sharesock",
+    "acl",
+    "cifsacl",
+    "idsfromsid",
+    "handletimeout",
+    "resilienthandles",
+    "persistenthandles",
+    "rdma",
+    "multichannel",
+    "max_channels",
+    "fsc",
+    "nocase",
+    "ignorecase",
+    "strictsync",
+    "strictcache",
+    "forcemandatorylock",
+    "mfsymlinks",
+    "autotune",
+    "handlecache",
+    "cred",
+    "domainauto",
+    "setuids",
+    "dynperm",
+    "user_xattr",
+    "intr",
+    "nolease",
+    "nosharesock",
+    "noblocksend",
+    "nodfs",
+    "posixpaths",
+    "rwpidforward",
+    "bsize",
+    "max_credits",
+    "prefixpath",
+    "backupuid",
+    "backupgid",
+    "acregmax",
+    "acdirmax",
+    "linux",
+    "posix",
+    "modefromsid",
+    "locallease",
+    "sign",
+    "retrans",
+    "esize",
+    "compress",
+    "sloppy",
+];
+
+const SWAP_OPTIONS: &[&str] = &["sw", "pri", "discard"];
+
+/// Filesystem specific options by type; a `no` prefix is accepted on all of them
+const FS_OPTIONS: &[(&str, &[&str])] = &[
+    ("ext2", EXT_OPTIONS),
+    ("ext3", EXT_OPTIONS),
+    ("ext4", EXT_OPTIONS),
+    ("xfs", XFS_OPTIONS),
+    ("btrfs", BTRFS_OPTIONS),
+    ("vfat", VFAT_OPTIONS),
+    ("tmpfs", TMPFS_OPTIONS),
+    ("nfs", NFS_OPTIONS),
+    ("nfs4", NFS_OPTIONS),
+    ("cifs", CIFS_OPTIONS),
+    ("smb3", CIFS_OPTIONS),
+    ("swap", SWAP_OPTIONS),
+];
+
+/// A best practice checked by the linter
+///
+/// With the `serde` feature, a rule is serialized as its identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Rule {
+    /// The root filesystem should be checked first, with `fsck` 1, unless fsck
+    /// can not check it, like tmpfs
+    RootFsck,
+    /// Filesystems other than root should use `fsck` 2, so they are checked after it
+    NonRootFsck,
+    /// Network filesystems need `_netdev` or `nofail` so boot does not wait on the network
+    NetworkWithoutNetdev,
+    /// Swap entries should use `none` as mount point
+    SwapMountPoint,
+    /// Kernel names like `/dev/sda1` change with probe order; tags do not
+    UnstableDevicePath,
+    /// A flag and its negation are both given, as in `ro,rw`
+    ConflictingOptions,
+    /// An option that the filesystem type does not know
+    UnknownOption,
+}
+
+impl Rule {
+    /// Every rule, in the order they are checked
+    pub const ALL: &'static [Rule] = &[
+        Rule::RootFsck,
+        Rule::NonRootFsck,
+        Rule::NetworkWithoutNetdev,
+        Rule::SwapMountPoint,
+        Rule::UnstableDevicePath,
+        Rule::ConflictingOptions,
+        Rule::UnknownOption,
+    ];
+
+    /// The identifier of the rule, such as `root-fsck`
+    pub fn id(self) -> &'static str {
+        match self {
+            Rule::RootFsck => "root-fsck",
+            Rule::NonRootFsck => "non-root-fsck",
+            Rule::NetworkWithoutNetdev => "network-without-netdev",
+            Rule::SwapMountPoint => "swap-mount-point",
+            Rule::UnstableDevicePath => "unstable-device-path",
+            Rule::ConflictingOptions => "conflicting-options",
+            Rule::UnknownOption => "unknown-option",
+        }
+    }
+
+    /// The rule with the identifier `id`
+    pub fn from_id(id: &str) -> Option<Rule> {
+        Rule::ALL.iter().cloned().find(|r| r.id() == id)
+    }
+
+    /// How serious a violation of the rule is
+    pub fn severity(self) -> Severity {
+        match self {
+            Rule::ConflictingOptions => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// A rule violated by an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Lint {
+    /// Index of the entry in the linted list
+    pub index: usize,
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    /// Writes `warning[rule-id]: message`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.rule, self.message)
+    }
+}
+
+/// Checks entries against the rules that are enabled; all of them by default
+#[derive(Debug, Clone, Default)]
+pub struct Linter {
+    disabled: HashSet<Rule>,
+}
+
+impl Linter {
+    /// Create a linter with every rule enabled
+    pub fn new() -> Linter {
+        Linter::default()
+    }
+
+    /// Turn off `rule`
+    pub fn disable(mut self, rule: Rule) -> Linter {
+        self.disabled.insert(rule);
+        self
+    }
+
+    /// Turn `rule` back on
+    pub fn enable(mut self, rule: Rule) -> Linter {
+        self.disabled.remove(&rule);
+        self
+    }
+
+    /// Returns `true` if `rule` is checked
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+
+    /// Check every entry, returning the violations in entry order
+    pub fn lint(&self, entries: &[Fstab]) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            for &rule in Rule::ALL {
+                if !self.is_enabled(rule) {
+                    continue;
+                }
+                for message in check_rule(rule, entry) {
+                    lints.push(Lint {
+                        index,
+                        rule,
+                        severity: rule.severity(),
+                        message,
+                    });
+                }
+            }
+        }
+        lints
+    }
+}
+
+/// Check entries against every rule
+pub fn lint(entries: &[Fstab]) -> Vec<Lint> {
+    Linter::new().lint(entries)
+}
+
+/// The messages for each violation of `rule` by `entry`
+fn check_rule(rule: Rule, entry: &Fstab) -> Vec<String> {
+    let dir = normalize_dir(&entry.dir);
+    let swap = entry.device_type == "swap";
+    match rule {
+        Rule::RootFsck if dir == "/" && entry.fsck != 1 && can_fsck(entry) => vec![format!(
+            "root filesystem has fsck pass {}, should be 1",
+            entry.fsck
+        )],
+        Rule::NonRootFsck if dir != "/" && entry.fsck == 1 && can_fsck(entry) => vec![format!(
+            "{} has fsck pass 1, which is meant for the root filesystem; use 2",
+            dir
+        )],
+        Rule::NetworkWithoutNetdev
+            if (entry.device.is_network()
+                || NETWORK_TYPES.contains(&entry.device_type.as_str()))
+                && !entry.options.contains("_netdev")
+                && !entry.options.contains("nofail") =>
+        {
+            vec![format!(
+                "network filesystem {} has neither _netdev nor nofail",
+                dir
+            )]
+        }
+        Rule::SwapMountPoint if swap && dir != "none" => vec![format!(
+            "swap has mount point {}, should be none",
+            entry.dir
+        )],
+        Rule::UnstableDevicePath if is_unstable(&entry.device) => vec![format!(
+            "{} may name another device after a reboot; use UUID= or another tag",
+            entry.device
+        )],
+        Rule::ConflictingOptions => conflicts(entry),
+        Rule::UnknownOption => unknown_options(entry),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns `true` if `device` is a kernel name like `/dev/sda1` or `/dev/nvme0n1p2`
+fn is_unstable(device: &Device) -> bool {
+    let name = match device.path().and_then(|p| p.strip_prefix("/dev/")) {
+        Some(name) => name,
+        None => return false,
+    };
+    UNSTABLE_NAMES.iter().any(|prefix| {
+        name.strip_prefix(prefix).map_or(false, |rest| {
+            rest.starts_with(|c: char| c.is_ascii_alphanumeric())
+                && rest.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+    })
+}
+
+/// Messages for flags given together with their negation
+fn conflicts(entry: &Fstab) -> Vec<String> {
+    let flags = entry
+        .options
+        .iter()
+        .filter(|o| o.value.is_none() && o.kind() != OptionKind::UserSpace)
+        .map(|o| o.name.as_str())
+        .collect::<Vec<_>>();
+    let mut pairs = Vec::new();
+    let mut messages = Vec::new();
+    for (i, &name) in flags.iter().enumerate() {
+        let off = negation(name);
+        if off.is_empty() || !flags[..i].contains(&off.as_str()) {
+            continue;
+        }
+        let mut pair = [name, off.as_str()];
+        pair.sort();
+        let pair = pair.join(",");
+        if !pairs.contains(&pair) {
+            messages.push(format!(
+                "{} and {} are both given; the last one wins",
+                off, name
+            ));
+            pairs.push(pair);
+        }
+    }
+    messages
+}
+
+/// Messages for filesystem options that the type does not know
+///
+/// Types without a list of options are not checked.
+fn unknown_options(entry: &Fstab) -> Vec<String> {
+    let known = match FS_OPTIONS.iter().find(|&&(t, _)| t == entry.device_type) {
+        Some(&(_, known)) => known,
+        None => return Vec::new(),
+    };
+    entry
+        .options
+        .iter()
+        .filter(|o| o.kind() == OptionKind::Filesystem)
+        .filter(|o| {
+            let name = o.name.as_str();
+            !known.contains(&name)
+                && !name
+                    .strip_prefix("no")
+                    .map_or(false, |n| known.contains(&n))
+        })
+        .map(|o| format!("{} is not an option of {}", o.name, entry.device_type))
+        .collect()
+}
+
+#[test]
+fn lint_rules() {
+    let entries = ::parse_fstab(concat!(
+        "UUID=0a1b2c3d / ext4 errors=remount-ro 0 1\n",
+        "/dev/sda2 /home ext4 defaults,noacl 0 1\n",
+        "LABEL=swap swap swap sw 0 0\n",
+        "nas:/export /mnt/nas nfs vers=4,soft 0 0\n",
+        "//fs/share /mnt/share cifs credentials=/etc/cred,_netdev,dynperm,nosetuids,nouser_xattr,mapposix,nomapchars,actimeo=1,echo_interval=60,nohandlecache 0 0\n",
+        "/dev/nvme0n1p3 /var xfs ro,noatime,rw,ro,nouuid,flavour=x 0 2\n",
+        "tmpfs / tmpfs size=1G,x-foo,rw,x-nofoo 0 0\n",
+        "/dev/disk/by-uuid/0a1b /srv auto defaults,weird 0 2\n",
+    ))
+    .unwrap();
+    let lints = lint(&entries)
+        .iter()
+        .map(|l| (l.index, l.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lints,
+        vec![
+            (
+                1,
+                "warning[non-root-fsck]: /home has fsck pass 1, which is meant for the root filesystem; use 2".to_owned()
+            ),
+            (
+                1,
+                "warning[unstable-device-path]: /dev/sda2 may name another device after a reboot; use UUID= or another tag".to_owned()
+            ),
+            (
+                2,
+                "warning[swap-mount-point]: swap has mount point swap, should be none".to_owned()
+            ),
+            (
+                3,
+                "warning[network-without-netdev]: network filesystem /mnt/nas has neither _netdev nor nofail".to_owned()
+            ),
+            (
+                5,
+                "warning[unstable-device-path]: /dev/nvme0n1p3 may name another device after a reboot; use UUID= or another tag".to_owned()
+            ),
+            (
+                5,
+                "error[conflicting-options]: ro and rw are both given; the last one wins".to_owned()
+            ),
+            (
+                5,
+                "warning[unknown-option]: flavour is not an option of xfs".to_owned()
+            ),
+        ]
+    );
+
+    // fsck passes are only asked for on filesystems fsck can check
+    let fsck_entries = ::parse_fstab(concat!(
+        "/dev/sda1 / ext4 defaults 0 0\n",
+        "tmpfs /tmp tmpfs defaults 0 1\n",
+        "nas:/export /mnt/nas nfs _netdev 0 1\n",
+    ))
+    .unwrap();
+    let lints = lint(&fsck_entries)
+        .iter()
+        .filter(|l| l.rule == Rule::RootFsck || l.rule == Rule::NonRootFsck)
+        .map(|l| (l.index, l.rule))
+        .collect::<Vec<_>>();
+    assert_eq!(lints, [(0, Rule::RootFsck)]);
+
+    let linter = Linter::new()
+        .disable(Rule::UnstableDevicePath)
+        .disable(Rule::RootFsck)
+        .disable(Rule::NonRootFsck)
+        .enable(Rule::NonRootFsck);
+    assert!(!linter.is_enabled(Rule::UnstableDevicePath));
+    let lints = linter.lint(&entries);
+    assert_eq!(lints.len(), 5);
+    assert!(lints.iter().all(|l| l.rule != Rule::UnstableDevicePath));
+    assert_eq!(lints[0].rule, Rule::NonRootFsck);
+
+    assert_eq!(
+        Rule::from_id("swap-mount-point"),
+        Some(Rule::SwapMountPoint)
+    );
+    assert_eq!(Rule::from_id("nope"), None);
+    assert_eq!(Rule::ConflictingOptions.severity(), Severity::Error);
+}
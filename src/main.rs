@@ -0,0 +1,461 @@
+extern crate fstab;
+
+use std::env;
+#[cfg(test)]
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+use fstab::{
+    Backup, ErrorType, Fstab, FstabDocument, Linter, Resolver, Rule, SaveOptions, Saved,
+    Severity,
+};
+
+const DEFAULT_FILE: &str = "/etc/fstab";
+
+const USAGE: &str = "\
+usage: fstab [<options>] [--] <command> [<args>]
+
+commands:
+    list                                  print the entries, aligned in columns
+    get <mountpoint> [--field <name>]     print the entry mounted at <mountpoint>
+    add <spec> <mountpoint> <type> [<options> [<dump> [<pass>]]]
+                                          append an entry
+    remove <mountpoint>                   remove the entry mounted at <mountpoint>
+    set-option <mountpoint> [--remove] <option>...
+                                          set, or remove, mount options of an entry
+    check [--root <dir>] [--disable <rule>]...
+                                          verify the entries against the running system,
+                                          or the tree at <dir>, and report lint warnings
+    fmt [--check]                         align the columns of the entries
+
+options:
+    -f, --file <path>    operate on <path> instead of /etc/fstab
+    -b, --backup         keep the replaced file as <path>.bak
+    -n, --dry-run        print the change as a unified diff instead of writing it
+    -h, --help           print this help
+
+Fields for --field are spec, file, vfstype, mntops, freq and passno.";
+
+/// Why a command did not succeed
+enum Failure {
+    /// The command line is wrong; the usage is shown
+    Usage(String),
+    /// The command failed, with the exit status to use
+    Failed(String, i32),
+    /// The command ran and found something to report, like a missing entry
+    Status(i32),
+}
+
+impl From<fstab::Error> for Failure {
+    fn from(e: fstab::Error) -> Failure {
+        Failure::Failed(format!("{:#}", e), 1)
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Failure {
+        Failure::Failed(e.to_string(), 1)
+    }
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let stdout = io::stdout();
+    let status = match run(&args, &mut stdout.lock()) {
+        Ok(()) => 0,
+        Err(Failure::Usage(message)) => {
+            eprintln!("fstab: {}\n\n{}", message, USAGE);
+            2
+        }
+        Err(Failure::Failed(message, status)) => {
+            eprintln!("fstab: {}", message);
+            status
+        }
+        Err(Failure::Status(status)) => status,
+    };
+    process::exit(status);
+}
+
+/// The command line, split into the file to work on, the command and its arguments
+struct Command<'a> {
+    file: &'a str,
+    save: SaveOptions,
+    name: &'a str,
+    args: Vec<&'a str>,
+}
+
+fn parse_args(args: &[String]) -> Result<Option<Command<'_>>, Failure> {
+    let mut file = DEFAULT_FILE;
+    let mut save = SaveOptions::new();
+    let mut iter = args.iter();
+    // Options end at the command, or at `--`; what follows is the command's
+    let name = loop {
+        let arg = match iter.next() {
+            Some(arg) => arg.as_str(),
+            None => return Err(Failure::Usage("no command given".to_owned())),
+        };
+        match arg {
+            "-h" | "--help" => return Ok(None),
+            "-b" | "--backup" => save = save.with_backup(Backup::Bak),
+            "-n" | "--dry-run" => save = save.with_dry_run(true),
+            "-f" | "--file" => match iter.next() {
+                Some(path) => file = path,
+                None => return Err(Failure::Usage(format!("{} needs a path", arg))),
+            },
+            "--" => match iter.next() {
+                Some(name) => break name.as_str(),
+                None => return Err(Failure::Usage("no command given".to_owned())),
+            },
+            _ if arg.starts_with('-') => {
+                return Err(Failure::Usage(format!("unknown option {}", arg)))
+            }
+            _ => break arg,
+        }
+    };
+    Ok(Some(Command {
+        file,
+        save,
+        name,
+        args: iter.map(String::as_str).collect(),
+    }))
+}
+
+fn run(args: &[String], out: &mut dyn Write) -> Result<(), Failure> {
+    let command = match parse_args(args)? {
+        Some(command) => command,
+        None => {
+            writeln!(out, "{}", USAGE)?;
+            return Ok(());
+        }
+    };
+    let mut doc = match FstabDocument::open(Some(command.file)) {
+        Ok(doc) => doc,
+        // `add` creates the file
+        Err(ref e)
+            if command.name == "add" && matches!(*e.reason(), ErrorType::FstabNotExist(_)) =>
+        {
+            FstabDocument::default()
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let args = &command.args[..];
+    match command.name {
+        "list" => {
+            expect_args(args, 0, 0)?;
+            write!(out, "{}", fstab::fstab_to_string(&doc.to_fstab()))?;
+        }
+        "get" => get(&doc, args, out)?,
+        "add" => {
+            expect_args(args, 3, 6)?;
+            if doc.find(args[1]).is_some() {
+                return Err(Failure::Failed(
+                    format!("{}: an entry for {} already exists", command.file, args[1]),
+                    1,
+                ));
+            }
+            doc.push(new_entry(args)?);
+            save(&doc, &command, out)?;
+        }
+        "remove" => {
+            expect_args(args, 1, 1)?;
+            if doc.remove(args[0]).is_none() {
+                return Err(not_found(command.file, args[0]));
+            }
+            save(&doc, &command, out)?;
+        }
+        "set-option" => {
+            set_option(&mut doc, command.file, args)?;
+            save(&doc, &command, out)?;
+        }
+        "check" => check(&doc, command.file, args, out)?,
+        "fmt" => {
+            let check_only = match *args {
+                [] => false,
+                ["--check"] => true,
+                _ => return Err(Failure::Usage("fmt takes only --check".to_owned())),
+            };
+            let before = doc.to_string();
+            doc.align();
+            if doc.to_string() != before {
+                if check_only {
+                    writeln!(out, "{}: not formatted", command.file)?;
+                    return Err(Failure::Status(1));
+                }
+                save(&doc, &command, out)?;
+            }
+        }
+        name => return Err(Failure::Usage(format!("unknown command {}", name))),
+    }
+    Ok(())
+}
+
+fn expect_args(args: &[&str], min: usize, max: usize) -> Result<(), Failure> {
+    if args.len() < min || args.len() > max {
+        return Err(Failure::Usage(format!(
+            "expected {} arguments, got {}",
+            if min == max {
+                min.to_string()
+            } else {
+                format!("{} to {}", min, max)
+            },
+            args.len()
+        )));
+    }
+    Ok(())
+}
+
+fn not_found(file: &str, dir: &str) -> Failure {
+    Failure::Failed(format!("{}: no entry for {}", file, dir), 1)
+}
+
+fn save(doc: &FstabDocument, command: &Command, out: &mut dyn Write) -> Result<(), Failure> {
+    if let Saved::DryRun(diff) = doc.save(Some(command.file), &command.save)? {
+        write!(out, "{}", diff)?;
+    }
+    Ok(())
+}
+
+fn get(doc: &FstabDocument, args: &[&str], out: &mut dyn Write) -> Result<(), Failure> {
+    let (dir, field) = match *args {
+        [dir] => (dir, None),
+        [dir, "--field", field] | ["--field", field, dir] => (dir, Some(field)),
+        _ => return Err(Failure::Usage("get takes <mountpoint> [--field <name>]".to_owned())),
+    };
+    let entry = match doc.find(dir) {
+        Some(entry) => entry,
+        None => return Err(Failure::Status(1)),
+    };
+    match field {
+        None => writeln!(out, "{}", entry)?,
+        Some("spec") => writeln!(out, "{}", entry.device)?,
+        Some("file") => writeln!(out, "{}", entry.dir)?,
+        Some("vfstype") => writeln!(out, "{}", entry.device_type)?,
+        Some("mntops") if entry.options.is_empty() => writeln!(out, "defaults")?,
+        Some("mntops") => writeln!(out, "{}", entry.options)?,
+        Some("freq") => writeln!(out, "{}", entry.dump as u8)?,
+        Some("passno") => writeln!(out, "{}", entry.fsck)?,
+        Some(name) => return Err(Failure::Usage(format!("unknown field {}", name))),
+    }
+    Ok(())
+}
+
+/// Build an entry from the arguments of `add`, filling in the fields left out
+fn new_entry(args: &[&str]) -> Result<Fstab, Failure> {
+    let number = |i: usize| match args.get(i) {
+        Some(n) => n
+            .parse::<usize>()
+            .map_err(|_| Failure::Usage(format!("invalid number {}", n))),
+        None => Ok(0),
+    };
+    Ok(Fstab {
+        device: args[0].parse().unwrap_or_else(|e| match e {}),
+        dir: args[1].to_owned(),
+        device_type: args[2].to_owned(),
+        options: args.get(3).map(|o| (*o).into()).unwrap_or_default(),
+        dump: number(4)? != 0,
+        fsck: number(5)?,
+    })
+}
+
+fn set_option(doc: &mut FstabDocument, file: &str, args: &[&str]) -> Result<(), Failure> {
+    let (dir, remove, options) = match *args {
+        [dir, "--remove", ref options @ ..] | ["--remove", dir, ref options @ ..] => {
+            (dir, true, options)
+        }
+        [dir, ref options @ ..] => (dir, false, options),
+        [] => return Err(Failure::Usage("set-option needs a mount point".to_owned())),
+    };
+    if options.is_empty() {
+        return Err(Failure::Usage("set-option needs an option".to_owned()));
+    }
+    // Catch global options given after the command, like a misplaced --dry-run
+    if let Some(option) = options.iter().find(|o| {
+        o.is_empty() || o.starts_with('-') || o.contains(|c: char| c.is_whitespace() || c == ',')
+    }) {
+        return Err(Failure::Usage(format!("invalid mount option {:?}", option)));
+    }
+    let entry = match doc.find_mut(dir) {
+        Some(entry) => entry,
+        None => return Err(not_found(file, dir)),
+    };
+    for option in options {
+        if remove {
+            entry.options.remove(option);
+            continue;
+        }
+        match option.find('=') {
+            Some(i) => entry.options.set(&option[..i], &option[i + 1..]),
+            None => entry.options.set_flag(option, true),
+        }
+    }
+    Ok(())
+}
+
+fn check(
+    doc: &FstabDocument,
+    file: &str,
+    args: &[&str],
+    out: &mut dyn Write,
+) -> Result<(), Failure> {
+    let mut linter = Linter::new();
+    let mut root = "/";
+    let mut iter = args.iter();
+    while let Some(&arg) = iter.next() {
+        match (arg, iter.next()) {
+            ("--disable", Some(id)) => {
+                let rule = Rule::from_id(id)
+                    .ok_or_else(|| Failure::Usage(format!("unknown rule {}", id)))?;
+                linter = linter.disable(rule);
+            }
+            ("--root", Some(dir)) => root = dir,
+            _ => {
+                return Err(Failure::Usage(
+                    "check takes only --root <dir> and --disable <rule>".to_owned(),
+                ))
+            }
+        }
+    }
+
+    // Line numbers of the entries, to point at them in the report
+    let lines = doc
+        .lines()
+        .iter()
+        .enumerate()
+        .filter(|&(_, l)| l.entry().is_some())
+        .map(|(n, _)| n + 1)
+        .collect::<Vec<_>>();
+    let entries = doc.to_fstab();
+    let mut report = fstab::verify(&entries, &Resolver::with_root(root))
+        .into_iter()
+        .map(|p| (p.index, Severity::Error, format!("error: {}", p)))
+        .chain(
+            linter
+                .lint(&entries)
+                .into_iter()
+                .map(|l| (l.index, l.severity, l.to_string())),
+        )
+        .collect::<Vec<_>>();
+    report.sort_by_key(|&(index, _, _)| index);
+    for &(index, _, ref message) in &report {
+        writeln!(out, "{}:{}: {}", file, lines[index], message)?;
+    }
+    if report.iter().any(|&(_, severity, _)| severity == Severity::Error) {
+        return Err(Failure::Status(1));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn run_on(file: &std::path::Path, args: &[&str]) -> (Result<(), Failure>, String) {
+    let mut full = vec!["--file".to_owned(), file.to_string_lossy().into_owned()];
+    full.extend(args.iter().map(|a| a.to_string()));
+    let mut out = Vec::new();
+    let result = run(&full, &mut out);
+    (result, String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn edit_commands() {
+    let dir = env::temp_dir().join(format!("fstab-test-cli-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("fstab");
+    fs::write(
+        &file,
+        "# root\nUUID=0a1b2c3d  /      ext4  errors=remount-ro  0  1\n",
+    )
+    .unwrap();
+
+    let (result, out) = run_on(&file, &["get", "/", "--field", "mntops"]);
+    assert!(result.is_ok());
+    assert_eq!(out, "errors=remount-ro\n");
+    assert!(matches!(
+        run_on(&file, &["get", "/home"]).0,
+        Err(Failure::Status(1))
+    ));
+
+    assert!(run_on(&file, &["add", "LABEL=home", "/home", "ext4"]).0.is_ok());
+    assert!(run_on(&file, &["add", "LABEL=home", "/home", "ext4"]).0.is_err());
+    assert!(run_on(&file, &["add", "LABEL=home", "/home/", "ext4"]).0.is_err());
+    assert!(run_on(&file, &["set-option", "/home", "noatime", "commit=60"]).0.is_ok());
+    assert!(run_on(&file, &["set-option", "/", "--remove", "errors"]).0.is_ok());
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "# root\nUUID=0a1b2c3d  /      ext4  defaults           0  1\n\
+         LABEL=home     /home  ext4  defaults,noatime,commit=60 0 0\n"
+    );
+
+    let (result, out) = run_on(&file, &["fmt", "--check"]);
+    assert!(matches!(result, Err(Failure::Status(1))));
+    assert!(out.ends_with(": not formatted\n"));
+    assert!(run_on(&file, &["fmt"]).0.is_ok());
+    assert!(run_on(&file, &["fmt", "--check"]).0.is_ok());
+    let (_, out) = run_on(&file, &["list"]);
+    assert_eq!(
+        out,
+        "UUID=0a1b2c3d /     ext4 defaults                   0 1\n\
+         LABEL=home    /home ext4 defaults,noatime,commit=60 0 0\n"
+    );
+
+    let (result, out) = run_on(&file, &["--dry-run", "remove", "/home"]);
+    assert!(result.is_ok());
+    assert!(out.ends_with("\n-LABEL=home    /home ext4 defaults,noatime,commit=60 0 0\n"));
+    assert!(run_on(&file, &["--backup", "remove", "/home"]).0.is_ok());
+    assert_eq!(
+        fs::read_to_string(dir.join("fstab.bak")).unwrap().lines().count(),
+        3
+    );
+    assert!(run_on(&file, &["remove", "/home"]).0.is_err());
+    assert!(matches!(run_on(&file, &["frobnicate"]).0, Err(Failure::Usage(_))));
+    assert!(matches!(run_on(&file, &["add", "x"]).0, Err(Failure::Usage(_))));
+
+    // Global options go before the command; after it, they are the command's arguments
+    let before = fs::read_to_string(&file).unwrap();
+    for option in &["-n", "", "a,b", "a b"] {
+        let (result, out) = run_on(&file, &["set-option", "/", option]);
+        assert!(matches!(result, Err(Failure::Usage(_))));
+        assert_eq!(out, "");
+    }
+    assert_eq!(fs::read_to_string(&file).unwrap(), before);
+    assert!(run_on(&file, &["-n", "--", "list"]).0.is_ok());
+    assert!(matches!(run_on(&file, &["--"]).0, Err(Failure::Usage(_))));
+    assert!(matches!(run_on(&file, &["-x", "list"]).0, Err(Failure::Usage(_))));
+
+    // add creates a missing file; other commands fail on it
+    let new = dir.join("new");
+    assert!(run_on(&new, &["list"]).0.is_err());
+    assert!(run_on(&new, &["add", "tmpfs", "/tmp", "tmpfs"]).0.is_ok());
+    assert_eq!(
+        fs::read_to_string(&new).unwrap(),
+        "tmpfs\t/tmp\ttmpfs\tdefaults\t0\t0\n"
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn check_command() {
+    let dir = env::temp_dir().join(format!("fstab-test-check-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("root/srv")).unwrap();
+    let file = dir.join("fstab");
+    fs::write(&file, "# data\ntmpfs /srv tmpfs defaults 0 0\n").unwrap();
+    let root = dir.join("root");
+    let root = root.to_str().unwrap();
+
+    let (result, out) = run_on(&file, &["check", "--root", root]);
+    assert!(result.is_ok());
+    assert_eq!(out, "");
+
+    fs::remove_dir(dir.join("root/srv")).unwrap();
+    let (result, out) = run_on(&file, &["check", "--root", root]);
+    assert!(matches!(result, Err(Failure::Status(1))));
+    assert!(out.ends_with(":2: error: mount point does not exist: /srv\n"));
+    assert!(matches!(
+        run_on(&file, &["check", "--root"]).0,
+        Err(Failure::Usage(_))
+    ));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
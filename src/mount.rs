@@ -0,0 +1,586 @@
+use std::error;
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt};
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+
+use libc;
+
+use plan::is_under;
+use table::{match_fstype, match_options};
+use {
+    normalize_dir, open_fstab, open_mountinfo, plan, probe, Device, Environment, Error, Fstab,
+    MountCycle, MountFlags, MountInfo, ResolveError, Resolver,
+};
+
+/// Mode of directories created for `X-mount.mkdir` without a value
+const MKDIR_MODE: u32 = 0o755;
+
+/// Why mounting failed
+///
+/// With the `serde` feature, it is serialized like `ErrorType`; the I/O error
+/// of a failed mount(2) call is kept as its message.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "kind", content = "detail", rename_all = "snake_case")
+)]
+pub enum MountError {
+    /// Reading fstab or the mounted filesystems, or creating the mount point, failed
+    Io(Error),
+    /// The entries depend on each other, so there is no order to mount them in
+    Cycle(MountCycle),
+    /// The device of the entry was not found
+    Resolve(ResolveError),
+    /// No known filesystem was found on the device of an `auto` entry
+    UnknownFilesystem(String),
+    /// The mode of `X-mount.mkdir` is not an octal number
+    InvalidMode(String),
+    /// The mount(2) call for the mount point failed
+    Failed {
+        target: String,
+        #[cfg_attr(feature = "serde", serde(with = "::serialize::io_message"))]
+        error: Arc<io::Error>,
+    },
+}
+
+impl fmt::Display for MountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MountError::Io(ref e) => e.fmt(f),
+            MountError::Cycle(ref e) => e.fmt(f),
+            MountError::Resolve(ref e) => e.fmt(f),
+            MountError::UnknownFilesystem(ref s) => write!(f, "unknown filesystem on {}", s),
+            MountError::InvalidMode(ref s) => write!(f, "invalid X-mount.mkdir mode: {}", s),
+            MountError::Failed {
+                ref target,
+                ref error,
+            } => write!(f, "mount failed: {}: {}", target, error),
+        }
+    }
+}
+
+impl error::Error for MountError {}
+
+impl From<Error> for MountError {
+    fn from(e: Error) -> MountError {
+        MountError::Io(e)
+    }
+}
+
+impl From<MountCycle> for MountError {
+    fn from(e: MountCycle) -> MountError {
+        MountError::Cycle(e)
+    }
+}
+
+impl From<ResolveError> for MountError {
+    fn from(e: ResolveError) -> MountError {
+        MountError::Resolve(e)
+    }
+}
+
+/// What mounting an entry did
+#[derive(Debug, Clone)]
+pub enum MountOutcome {
+    /// The entry was mounted
+    Mounted,
+    /// The device was already mounted at the mount point
+    AlreadyMounted,
+    /// The entry was not mounted: it is `noauto`, is left out by the type or
+    /// option filters, or has no mount point, like swap
+    Skipped,
+    /// Mounting failed
+    Failed(MountError),
+    /// The device of a `nofail` entry does not exist
+    Ignored(MountError),
+}
+
+impl MountOutcome {
+    /// Returns `true` unless mounting failed
+    pub fn is_ok(&self) -> bool {
+        !matches!(*self, MountOutcome::Failed(_))
+    }
+}
+
+impl fmt::Display for MountOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MountOutcome::Mounted => f.write_str("mounted"),
+            MountOutcome::AlreadyMounted => f.write_str("already mounted"),
+            MountOutcome::Skipped => f.write_str("skipped"),
+            MountOutcome::Failed(ref e) => write!(f, "failed: {}", e),
+            MountOutcome::Ignored(ref e) => write!(f, "ignored: {}", e),
+        }
+    }
+}
+
+/// The outcome of mounting one entry
+#[derive(Debug, Clone)]
+pub struct MountReport {
+    /// Index of the entry in the list that was mounted
+    pub index: usize,
+    pub outcome: MountOutcome,
+}
+
+/// Mounts entries with mount(2), like `mount -a`
+///
+/// Entries are mounted in the order of [`plan`](fn.plan.html). The source
+/// of a tag is resolved to its device node, and a type of `auto` is probed
+/// from the device; a list of types is tried in order. The options are
+/// passed as the flags and data string of
+/// [`effective`](struct.MountOptions.html#method.effective), with the
+/// propagation set by a second call, and a read-only bind mount is
+/// remounted to apply its flags. Network sources are passed as written;
+/// helpers such as `mount.nfs` are not run.
+///
+/// `noauto` entries are skipped, as are entries whose device is already
+/// mounted at the mount point. A missing mount point is created if the
+/// entry has `X-mount.mkdir[=mode]`. A missing device of a `nofail` entry
+/// is not a failure.
+#[derive(Debug, Clone, Default)]
+pub struct Mounter {
+    types: Option<String>,
+    options: Option<String>,
+}
+
+impl Mounter {
+    /// Mount every entry that is not `noauto`
+    pub fn new() -> Mounter {
+        Mounter::default()
+    }
+
+    /// Only mount entries whose type matches a type list, as `mount -t` takes it
+    ///
+    /// See [`match_fstype`](fn.match_fstype.html).
+    pub fn with_types(mut self, pattern: &str) -> Mounter {
+        self.types = Some(pattern.to_owned());
+        self
+    }
+
+    /// Only mount entries whose options match a pattern, as `mount -O` takes it
+    ///
+    /// See [`match_options`](fn.match_options.html).
+    pub fn with_options(mut self, pattern: &str) -> Mounter {
+        self.options = Some(pattern.to_owned());
+        self
+    }
+
+    /// Mount `entries`, resolving devices through `env`
+    ///
+    /// Returns a report for every entry, in the order they were mounted,
+    /// followed by the entries without a mount point. Fails only if the
+    /// entries can not be ordered.
+    pub fn mount<E: Environment + ?Sized>(
+        &self,
+        entries: &[Fstab],
+        env: &E,
+    ) -> Result<Vec<MountReport>, MountCycle> {
+        let order = plan(entries)?.order();
+        let mut reports = order
+            .iter()
+            .map(|&index| MountReport {
+                index,
+                outcome: self.mount_entry(&entries[index], env),
+            })
+            .collect::<Vec<_>>();
+        reports.extend(
+            (0..entries.len())
+                .filter(|i| !order.contains(i))
+                .map(|index| MountReport {
+                    index,
+                    outcome: MountOutcome::Skipped,
+                }),
+        );
+        Ok(reports)
+    }
+
+    fn wanted(&self, entry: &Fstab) -> bool {
+        !entry.options.contains("noauto")
+            && self
+                .types
+                .as_ref()
+                .map_or(true, |t| match_fstype(&entry.device_type, t))
+            && self
+                .options
+                .as_ref()
+                .map_or(true, |o| match_options(&entry.options, o))
+    }
+
+    fn mount_entry<E: Environment + ?Sized>(&self, entry: &Fstab, env: &E) -> MountOutcome {
+        if !self.wanted(entry) {
+            return MountOutcome::Skipped;
+        }
+        match mount_one(entry, env) {
+            Ok(true) => MountOutcome::Mounted,
+            Ok(false) => MountOutcome::AlreadyMounted,
+            Err(e) => match e {
+                MountError::Resolve(ResolveError::NotFound(_))
+                    if entry.options.contains("nofail") =>
+                {
+                    MountOutcome::Ignored(e)
+                }
+                _ => MountOutcome::Failed(e),
+            },
+        }
+    }
+}
+
+/// Mount the entries of `/etc/fstab`, like `mount -a`
+pub fn mount_all() -> Result<Vec<MountReport>, MountError> {
+    Ok(Mounter::new().mount(&open_fstab(None)?, &Resolver::new())?)
+}
+
+/// Mount one entry; returns `false` if it was already mounted
+fn mount_one<E: Environment + ?Sized>(entry: &Fstab, env: &E) -> Result<bool, MountError> {
+    let dir = normalize_dir(&entry.dir);
+    let bind = entry.options.contains("bind") || entry.options.contains("rbind");
+    let source = source(&entry.device, bind, env)?;
+    if is_mounted(entry, &dir, &source, bind, &open_mountinfo(None)?) {
+        return Ok(false);
+    }
+    if !Path::new(&dir).exists() {
+        if let Some(option) = entry.options.option("X-mount.mkdir") {
+            let mode = match option.value {
+                Some(ref v) if !v.is_empty() => {
+                    u32::from_str_radix(v, 8).map_err(|_| MountError::InvalidMode(v.clone()))?
+                }
+                _ => MKDIR_MODE,
+            };
+            DirBuilder::new()
+                .recursive(true)
+                .mode(mode)
+                .create(&dir)
+                .map_err(|e| Error::from(e).with_path(&dir))?;
+        }
+    }
+
+    let effective = entry.options.effective();
+    let mut result = Err(MountError::UnknownFilesystem(source.clone()));
+    for fs_type in entry.device_type.split(',') {
+        let fs_type = match fs_type {
+            _ if bind => "none".to_owned(),
+            "auto" => match probe(&source) {
+                Ok(Some(superblock)) => superblock.fs_type,
+                Ok(None) => {
+                    result = Err(MountError::UnknownFilesystem(source.clone()));
+                    continue;
+                }
+                Err(e) => {
+                    result = Err(e.into());
+                    continue;
+                }
+            },
+            t => t.to_owned(),
+        };
+        result = sys_mount(&source, &dir, &fs_type, effective.flags, &effective.data);
+        if result.is_ok() || bind {
+            break;
+        }
+    }
+    result?;
+
+    let extra = effective.flags & !(MountFlags::BIND | MountFlags::REC);
+    if bind && !extra.is_empty() {
+        // A bind mount takes its flags from the source until it is remounted
+        let locked = current_flags(&dir).map_err(|e| Error::from(e).with_path(&dir))?
+            & (MountFlags::NOSUID | MountFlags::NODEV | MountFlags::NOEXEC)
+            & !entry.options.mentioned_flags();
+        sys_mount(
+            "none",
+            &dir,
+            "none",
+            extra | locked | MountFlags::REMOUNT | MountFlags::BIND,
+            "",
+        )?;
+    }
+    if !effective.propagation.is_empty() {
+        sys_mount("none", &dir, "none", effective.propagation, "")?;
+    }
+    Ok(true)
+}
+
+/// The source argument for mount(2)
+fn source<E: Environment + ?Sized>(
+    device: &Device,
+    bind: bool,
+    env: &E,
+) -> Result<String, ResolveError> {
+    match *device {
+        Device::MountPoint(ref p) if bind => Ok(p.clone()),
+        ref d if d.is_pseudo() || d.is_network() => Ok(d.to_string()),
+        ref d => {
+            let path = env.resolve(d)?;
+            if !path.exists() {
+                return Err(ResolveError::NotFound(d.clone()));
+            }
+            Ok(path.to_string_lossy().into_owned())
+        }
+    }
+}
+
+/// Returns `true` if `source` is mounted at `dir`
+///
+/// A bind mount is mounted if the filesystem and directory of its source
+/// are, a block device if the same device number is, and anything else if
+/// the same source is mounted with one of the entry's types.
+fn is_mounted(entry: &Fstab, dir: &str, source: &str, bind: bool, mounts: &[MountInfo]) -> bool {
+    let mut here = mounts
+        .iter()
+        .filter(|m| normalize_dir(&m.mount_point) == dir);
+    if bind {
+        let source = normalize_dir(source);
+        // The top mount of the deepest mount point holding the source
+        let holder = match mounts
+            .iter()
+            .filter(|m| is_under(&source, &normalize_dir(&m.mount_point)))
+            .max_by_key(|m| normalize_dir(&m.mount_point).len())
+        {
+            Some(holder) => holder,
+            None => return false,
+        };
+        let mount_point = normalize_dir(&holder.mount_point);
+        let relative = if mount_point == "/" {
+            &source[..]
+        } else {
+            &source[mount_point.len()..]
+        };
+        let root = normalize_dir(&format!("{}/{}", holder.root, relative));
+        return here.any(|m| {
+            (m.major, m.minor) == (holder.major, holder.minor) && normalize_dir(&m.root) == root
+        });
+    }
+    if let Ok(meta) = fs::metadata(source) {
+        if meta.file_type().is_block_device() {
+            let dev = meta.rdev();
+            let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+            let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+            return here.any(|m| (u64::from(m.major), u64::from(m.minor)) == (major, minor));
+        }
+    }
+    here.any(|m| {
+        m.source.to_string() == source
+            && entry
+                .device_type
+                .split(',')
+                .any(|t| t == "auto" || t == m.fs_type)
+    })
+}
+
+/// The flags `dir` is mounted with
+fn current_flags(dir: &str) -> io::Result<MountFlags> {
+    let path = c_string(dir)?;
+    let mut stat: libc::statvfs = unsafe { ::std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // The ST_* flags have the values of the MS_* flags they report
+    Ok(MountFlags::from_bits(stat.f_flag as u64))
+}
+
+fn sys_mount(
+    source: &str,
+    target: &str,
+    fs_type: &str,
+    flags: MountFlags,
+    data: &str,
+) -> Result<(), MountError> {
+    let failed = |error| MountError::Failed {
+        target: target.to_owned(),
+        error: Arc::new(error),
+    };
+    let source = c_string(source).map_err(failed)?;
+    let target_c = c_string(target).map_err(failed)?;
+    let fs_type = c_string(fs_type).map_err(failed)?;
+    let data = if data.is_empty() {
+        None
+    } else {
+        Some(c_string(data).map_err(failed)?)
+    };
+    let rc = unsafe {
+        libc::mount(
+            source.as_ptr(),
+            target_c.as_ptr(),
+            fs_type.as_ptr(),
+            flags.bits() as _,
+            data.as_ref()
+                .map_or(ptr::null(), |d| d.as_ptr() as *const libc::c_void),
+        )
+    };
+    if rc != 0 {
+        return Err(failed(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+fn c_string(s: &str) -> io::Result<CString> {
+    CString::new(s).map_err(io::Error::from)
+}
+
+/// Set, to the fixture directory, in the child process `in_namespace` starts
+#[cfg(test)]
+const NAMESPACE_VAR: &str = "FSTAB_TEST_NAMESPACE_DIR";
+
+/// Run the test `name` again in a child process, in a new user and mount
+/// namespace, with `NAMESPACE_VAR` set to `dir`
+///
+/// The child is the test binary running only that test, so it does not
+/// share the harness with other tests. It enters the namespaces between
+/// fork and exec, where only system calls are made. Nothing is run if the
+/// namespaces are not available.
+#[cfg(test)]
+fn in_namespace(name: &str, dir: &Path) {
+    use std::env;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let setgroups = CString::new("/proc/self/setgroups").unwrap();
+    let uid_map = CString::new("/proc/self/uid_map").unwrap();
+    let gid_map = CString::new("/proc/self/gid_map").unwrap();
+    let uid_line = format!("0 {} 1", uid);
+    let gid_line = format!("0 {} 1", gid);
+    let root = CString::new("/").unwrap();
+    let none = CString::new("none").unwrap();
+    let enter = move || unsafe {
+        if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        write_proc(&setgroups, b"deny")?;
+        write_proc(&uid_map, uid_line.as_bytes())?;
+        write_proc(&gid_map, gid_line.as_bytes())?;
+        let flags = libc::MS_REC | libc::MS_PRIVATE;
+        if libc::mount(
+            none.as_ptr(),
+            root.as_ptr(),
+            ptr::null(),
+            flags,
+            ptr::null(),
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    };
+
+    let mut child = Command::new(env::current_exe().unwrap());
+    child
+        .args([name, "--exact", "--test-threads=1"])
+        .env(NAMESPACE_VAR, dir);
+    unsafe {
+        child.pre_exec(enter);
+    }
+    let output = match child.output() {
+        Ok(output) => output,
+        // Disabled by the kernel, a sysctl or a seccomp filter
+        Err(ref e)
+            if [
+                libc::EPERM,
+                libc::EACCES,
+                libc::EINVAL,
+                libc::ENOSPC,
+                libc::ENOSYS,
+            ]
+            .iter()
+            .any(|&n| e.raw_os_error() == Some(n)) =>
+        {
+            return
+        }
+        Err(e) => panic!("{}", e),
+    };
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Write `data` to a file in `/proc` without allocating
+#[cfg(test)]
+unsafe fn write_proc(path: &CString, data: &[u8]) -> io::Result<()> {
+    let fd = libc::open(path.as_ptr(), libc::O_WRONLY);
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let written = libc::write(fd, data.as_ptr() as *const libc::c_void, data.len());
+    let error = io::Error::last_os_error();
+    libc::close(fd);
+    if written < 0 {
+        return Err(error);
+    }
+    Ok(())
+}
+
+#[test]
+fn mount_in_namespace() {
+    let dir = match ::std::env::var_os(NAMESPACE_VAR) {
+        Some(dir) => ::std::path::PathBuf::from(dir),
+        None => {
+            let dir = ::test_dir("mount");
+            fs::create_dir_all(dir.join("src")).unwrap();
+            fs::write(dir.join("src/hello"), "hi").unwrap();
+            for name in &["a", "c", "d", "e", "f"] {
+                fs::create_dir(dir.join(name)).unwrap();
+            }
+            in_namespace("mount::mount_in_namespace", &dir);
+            fs::remove_dir_all(&dir).unwrap();
+            return;
+        }
+    };
+
+    // In the namespace
+    let d = dir.to_str().unwrap();
+    let entries = ::parse_fstab(&format!(
+        concat!(
+            "tmpfs {0}/a tmpfs size=1m 0 0\n",
+            "tmpfs {0}/a/nested tmpfs X-mount.mkdir=0700 0 0\n",
+            "{0}/src {0}/b none bind,ro,X-mount.mkdir 0 0\n",
+            "tmpfs {0}/c tmpfs noauto 0 0\n",
+            "/dev/fstab-test-missing {0}/d ext4 nofail 0 0\n",
+            "/dev/fstab-test-missing {0}/e ext4 defaults 0 0\n",
+            "nas:/vol {0}/f nfs defaults 0 0\n",
+            "UUID=ffff none swap sw 0 0\n",
+        ),
+        d
+    ))
+    .unwrap();
+    let mounter = Mounter::new().with_types("nonfs");
+    let reports = mounter
+        .mount(&entries, &Resolver::new())
+        .unwrap()
+        .iter()
+        .map(|r| (r.index, r.outcome.to_string()))
+        .collect::<Vec<_>>();
+    let missing = "device not found: /dev/fstab-test-missing";
+    assert_eq!(
+        reports,
+        vec![
+            (0, "mounted".to_owned()),
+            (2, "mounted".to_owned()),
+            (3, "skipped".to_owned()),
+            (4, format!("ignored: {}", missing)),
+            (5, format!("failed: {}", missing)),
+            (6, "skipped".to_owned()),
+            (1, "mounted".to_owned()),
+            (7, "skipped".to_owned()),
+        ]
+    );
+    assert_eq!(fs::read_to_string(format!("{}/b/hello", d)).unwrap(), "hi");
+    assert!(fs::write(format!("{}/b/new", d), "").is_err());
+    let nested = fs::metadata(format!("{}/a/nested", d)).unwrap();
+    assert!(nested.is_dir());
+
+    let outcomes = mounter
+        .mount(&entries[..3], &Resolver::new())
+        .unwrap()
+        .iter()
+        .map(|r| r.outcome.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(outcomes, ["already mounted"; 3]);
+}
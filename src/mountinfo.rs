@@ -0,0 +1,407 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::str::FromStr;
+
+use {
+    parse_device, read_line, split_fields, unescape, Device, Error, ErrorType, Fstab, MountOption,
+    MountOptions, Result,
+};
+
+/// Default Path for `mountinfo`
+const MOUNTINFO_PATH: &str = "/proc/self/mountinfo";
+
+/// An optional field of a mountinfo line, describing mount propagation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionalField {
+    /// `shared:N`, the mount is shared in peer group N
+    Shared(u32),
+    /// `master:N`, the mount is a slave of peer group N
+    Master(u32),
+    /// `propagate_from:N`, the mount receives propagation from peer group N
+    PropagateFrom(u32),
+    /// `unbindable`, the mount is unbindable
+    Unbindable,
+    /// A field unknown to this crate
+    Other(String),
+}
+
+impl FromStr for OptionalField {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OptionalField> {
+        let (tag, value) = match s.find(':') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        Ok(match (tag, value) {
+            ("shared", Some(v)) => OptionalField::Shared(v.parse()?),
+            ("master", Some(v)) => OptionalField::Master(v.parse()?),
+            ("propagate_from", Some(v)) => OptionalField::PropagateFrom(v.parse()?),
+            ("unbindable", None) => OptionalField::Unbindable,
+            _ => OptionalField::Other(s.to_owned()),
+        })
+    }
+}
+
+impl fmt::Display for OptionalField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OptionalField::Shared(n) => write!(f, "shared:{}", n),
+            OptionalField::Master(n) => write!(f, "master:{}", n),
+            OptionalField::PropagateFrom(n) => write!(f, "propagate_from:{}", n),
+            OptionalField::Unbindable => f.write_str("unbindable"),
+            OptionalField::Other(ref s) => f.write_str(s),
+        }
+    }
+}
+
+/// Types for storing a line of `/proc/self/mountinfo`, see proc(5)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    /// Unique ID of the mount
+    pub mount_id: u32,
+    /// ID of the parent mount, or of itself at the top of the tree
+    pub parent_id: u32,
+    /// Major device number of files in this filesystem
+    pub major: u32,
+    /// Minor device number of files in this filesystem
+    pub minor: u32,
+    /// The directory of the filesystem which forms the root of this mount
+    pub root: String,
+    /// The mount point, relative to the root of the process
+    pub mount_point: String,
+    /// Per-mount options
+    pub mount_options: MountOptions,
+    /// Propagation fields such as `shared:N` and `master:N`
+    pub optional_fields: Vec<OptionalField>,
+    /// The type of the filesystem
+    pub fs_type: String,
+    /// The mounted device or remote filesystem
+    pub source: Device,
+    /// Per-superblock options
+    pub super_options: MountOptions,
+}
+
+impl MountInfo {
+    /// The entry as it would appear in `/proc/mounts`
+    ///
+    /// The options are the per-mount options followed by the super options
+    /// not already among them. The super block's `rw` or `ro` is not repeated,
+    /// but a read-only super block makes the mount `ro`.
+    pub fn to_fstab(&self) -> Fstab {
+        let read_only = self.super_options.contains("ro");
+        Fstab {
+            device: self.source.clone(),
+            dir: self.mount_point.clone(),
+            device_type: self.fs_type.clone(),
+            options: self
+                .mount_options
+                .iter()
+                .map(|o| match o.name.as_str() {
+                    "rw" if read_only => MountOption::new("ro"),
+                    _ => o.clone(),
+                })
+                .chain(
+                    self.super_options
+                        .iter()
+                        .filter(|o| {
+                            o.name != "rw"
+                                && o.name != "ro"
+                                && !self.mount_options.contains(&o.name)
+                        })
+                        .cloned(),
+                )
+                .collect(),
+            dump: false,
+            fsck: 0,
+        }
+    }
+
+    /// The peer group the mount is shared in, if any
+    pub fn shared(&self) -> Option<u32> {
+        self.optional_fields.iter().find_map(|f| match *f {
+            OptionalField::Shared(n) => Some(n),
+            _ => None,
+        })
+    }
+
+    /// The peer group the mount is a slave of, if any
+    pub fn master(&self) -> Option<u32> {
+        self.optional_fields.iter().find_map(|f| match *f {
+            OptionalField::Master(n) => Some(n),
+            _ => None,
+        })
+    }
+}
+
+impl FromStr for MountInfo {
+    type Err = Error;
+
+    /// Parse a single line of `/proc/self/mountinfo`
+    fn from_str(line: &str) -> Result<MountInfo> {
+        let fields = split_fields(line);
+        let end = line.trim_end().len();
+        let missing = |what: &str| {
+            Error::new(ErrorType::MalformedMountInfo(format!("missing {}", what)))
+                .with_span(end..end)
+                .with_text(line)
+        };
+        let number = |i: usize| {
+            let (start, f) = fields[i];
+            f.parse::<u32>().map_err(|e| {
+                Error::from(e)
+                    .with_span(start..start + f.len())
+                    .with_text(line)
+            })
+        };
+        if fields.len() < 6 {
+            return Err(missing(
+                [
+                    "mount ID",
+                    "parent ID",
+                    "major:minor",
+                    "root",
+                    "mount point",
+                    "mount options",
+                ][fields.len()],
+            ));
+        }
+        let (dev_start, dev) = fields[2];
+        let (major, minor) = match dev.find(':') {
+            Some(i) => (&dev[..i], &dev[i + 1..]),
+            None => {
+                return Err(Error::new(ErrorType::MalformedMountInfo(
+                    "expected major:minor".to_owned(),
+                ))
+                .with_span(dev_start..dev_start + dev.len())
+                .with_text(line))
+            }
+        };
+        let device_number = |n: &str, offset: usize| {
+            n.parse::<u32>().map_err(|e| {
+                let start = dev_start + offset;
+                Error::from(e)
+                    .with_span(start..start + n.len())
+                    .with_text(line)
+            })
+        };
+        let separator = fields[6..]
+            .iter()
+            .position(|&(_, f)| f == "-")
+            .map(|i| i + 6)
+            .ok_or_else(|| missing("separator `-`"))?;
+        let optional_fields = fields[6..separator]
+            .iter()
+            .map(|&(start, f)| {
+                f.parse::<OptionalField>()
+                    .map_err(|e| e.with_span(start..start + f.len()).with_text(line))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let rest = &fields[separator + 1..];
+        if rest.len() < 3 {
+            return Err(missing(
+                ["filesystem type", "source", "super options"][rest.len()],
+            ));
+        }
+        if rest.len() > 3 {
+            let start = rest[3].0;
+            return Err(
+                Error::new(ErrorType::TooManyFields(line[start..end].to_owned()))
+                    .with_span(start..end)
+                    .with_text(line),
+            );
+        }
+        Ok(MountInfo {
+            mount_id: number(0)?,
+            parent_id: number(1)?,
+            major: device_number(major, 0)?,
+            minor: device_number(minor, major.len() + 1)?,
+            root: unescape(fields[3].1),
+            mount_point: unescape(fields[4].1),
+            mount_options: MountOptions::from(fields[5].1),
+            optional_fields,
+            fs_type: unescape(rest[0].1),
+            source: parse_device(&unescape(rest[1].1)),
+            super_options: MountOptions::from(rest[2].1),
+        })
+    }
+}
+
+/// The mounts of a mountinfo table, linked by their parent IDs
+#[derive(Debug, Clone, Default)]
+pub struct MountTree {
+    mounts: Vec<MountInfo>,
+}
+
+impl MountTree {
+    /// Build the tree from a list of mounts
+    pub fn new(mounts: Vec<MountInfo>) -> MountTree {
+        MountTree { mounts }
+    }
+
+    /// All mounts, in the order of the table
+    pub fn mounts(&self) -> &[MountInfo] {
+        &self.mounts
+    }
+
+    /// The mount with the given ID
+    pub fn get(&self, mount_id: u32) -> Option<&MountInfo> {
+        self.mounts.iter().find(|m| m.mount_id == mount_id)
+    }
+
+    /// The parent of `mount`, if it is in the table
+    pub fn parent(&self, mount: &MountInfo) -> Option<&MountInfo> {
+        if mount.parent_id == mount.mount_id {
+            return None;
+        }
+        self.get(mount.parent_id)
+    }
+
+    /// The mounts whose parent is the mount with the given ID
+    pub fn children(&self, mount_id: u32) -> Vec<&MountInfo> {
+        self.mounts
+            .iter()
+            .filter(|m| m.parent_id == mount_id && m.mount_id != mount_id)
+            .collect()
+    }
+
+    /// The mounts whose parent is not in the table, usually only the root
+    pub fn roots(&self) -> Vec<&MountInfo> {
+        self.mounts
+            .iter()
+            .filter(|m| self.parent(m).is_none())
+            .collect()
+    }
+
+    /// The last mount at `mount_point`, which is the one that is visible
+    pub fn find(&self, mount_point: &str) -> Option<&MountInfo> {
+        self.mounts
+            .iter()
+            .rev()
+            .find(|m| m.mount_point == mount_point)
+    }
+}
+
+/// Open a mountinfo file and read it into a list of `MountInfo`
+/// When `path` is set to `None`, this function will use `/proc/self/mountinfo`.
+pub fn open_mountinfo(path: Option<&str>) -> Result<Vec<MountInfo>> {
+    let path = path.unwrap_or(MOUNTINFO_PATH);
+    File::open(path)
+        .map_err(Error::from)
+        .and_then(read_mountinfo)
+        .map_err(|e| e.with_path(path))
+}
+
+/// Parse the content of a mountinfo file into a list of `MountInfo`
+pub fn parse_mountinfo(content: &str) -> Result<Vec<MountInfo>> {
+    read_mountinfo(content.as_bytes())
+}
+
+/// Read mountinfo content from any reader into a list of `MountInfo`
+pub fn read_mountinfo<R: Read>(reader: R) -> Result<Vec<MountInfo>> {
+    let mut reader = BufReader::new(reader);
+    let mut mounts = Vec::new();
+    let mut n = 0;
+    while let Some(l) = read_line(&mut reader, &mut n)? {
+        if l.trim().is_empty() {
+            continue;
+        }
+        mounts.push(l.parse::<MountInfo>().map_err(|e| e.with_line(n))?);
+    }
+    Ok(mounts)
+}
+
+#[cfg(test)]
+const MOUNTINFO: &str = "\
+22 1 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:13 - proc proc rw
+1 0 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw,errors=remount-ro
+25 1 0:5 / /dev rw,nosuid,relatime shared:2 - devtmpfs udev rw,size=8133500k,mode=755
+36 25 0:23 / /dev/pts rw,nosuid,noexec,relatime shared:3 master:1 propagate_from:2 - devpts devpts rw,mode=600
+40 1 8:17 /exports/a\\040b /mnt/a\\040b rw,noatime master:4 unbindable - nfs4 server:/exports ro,vers=4.2
+";
+
+#[test]
+fn parse_mountinfo_lines() {
+    let mounts = parse_mountinfo(MOUNTINFO).unwrap();
+    assert_eq!(mounts.len(), 5);
+    let m = &mounts[3];
+    assert_eq!((m.mount_id, m.parent_id, m.major, m.minor), (36, 25, 0, 23));
+    assert_eq!(m.root, "/");
+    assert_eq!(m.mount_point, "/dev/pts");
+    assert_eq!(m.mount_options.flag("exec"), Some(false));
+    assert_eq!(
+        m.optional_fields,
+        vec![
+            OptionalField::Shared(3),
+            OptionalField::Master(1),
+            OptionalField::PropagateFrom(2),
+        ]
+    );
+    assert_eq!(m.shared(), Some(3));
+    assert_eq!(m.master(), Some(1));
+    assert_eq!(m.fs_type, "devpts");
+    assert_eq!(m.super_options.get("mode"), Some("600"));
+
+    let m = &mounts[4];
+    assert_eq!(m.root, "/exports/a b");
+    assert_eq!(m.mount_point, "/mnt/a b");
+    assert_eq!(m.shared(), None);
+    assert_eq!(m.optional_fields[1], OptionalField::Unbindable);
+    assert_eq!(
+        m.source,
+        Device::Nfs {
+            host: "server".to_owned(),
+            export: "/exports".to_owned(),
+        }
+    );
+
+    let fstab = mounts[1].to_fstab();
+    assert_eq!(fstab.device, Device::MountPoint("/dev/sda1".to_owned()));
+    assert_eq!(fstab.options.to_string(), "rw,relatime,errors=remount-ro");
+    assert_eq!(
+        mounts[4].to_fstab().options.to_string(),
+        "ro,noatime,vers=4.2"
+    );
+}
+
+#[test]
+fn mountinfo_errors() {
+    let e = parse_mountinfo("22 1 0:21 / /proc rw shared:13 proc proc rw\n").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "1:44: malformed mountinfo: missing separator `-`"
+    );
+    let e = parse_mountinfo("22 1 0:x / /proc rw - proc proc rw\n").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "1:8: invalid number: invalid digit found in string"
+    );
+    let e = parse_mountinfo("\n22 1 0:21 / /proc rw - proc proc\n").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "2:33: malformed mountinfo: missing super options"
+    );
+    let e = parse_mountinfo("22 1 0:21 / /proc rw shared:x - proc proc rw\n").unwrap_err();
+    assert_eq!(e.columns(), Some(22..30));
+}
+
+#[test]
+fn mount_tree() {
+    let tree = MountTree::new(parse_mountinfo(MOUNTINFO).unwrap());
+    let roots = tree.roots();
+    assert_eq!(roots.len(), 1);
+    assert_eq!(roots[0].mount_point, "/");
+    let children = tree
+        .children(1)
+        .iter()
+        .map(|m| m.mount_point.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(children, vec!["/proc", "/dev", "/mnt/a b"]);
+    let pts = tree.find("/dev/pts").unwrap();
+    assert_eq!(tree.parent(pts).unwrap().mount_point, "/dev");
+    assert!(tree.get(99).is_none());
+
+    let live = MountTree::new(open_mountinfo(None).unwrap());
+    assert!(!live.roots().is_empty());
+}
@@ -0,0 +1,418 @@
+use std::fmt;
+use std::iter::FromIterator;
+use std::slice;
+
+/// Filesystem independent options, as listed in mount(8)
+const GENERIC_OPTIONS: &[&str] = &[
+    "async",
+    "atime",
+    "noatime",
+    "auto",
+    "noauto",
+    "context",
+    "fscontext",
+    "defcontext",
+    "rootcontext",
+    "defaults",
+    "dev",
+    "nodev",
+    "diratime",
+    "nodiratime",
+    "dirsync",
+    "exec",
+    "noexec",
+    "group",
+    "nogroup",
+    "iversion",
+    "noiversion",
+    "mand",
+    "nomand",
+    "_netdev",
+    "nofail",
+    "relatime",
+    "norelatime",
+    "strictatime",
+    "nostrictatime",
+    "lazytime",
+    "nolazytime",
+    "suid",
+    "nosuid",
+    "silent",
+    "loud",
+    "owner",
+    "noowner",
+    "remount",
+    "ro",
+    "rw",
+    "sync",
+    "user",
+    "nouser",
+    "users",
+    "nousers",
+    "symfollow",
+    "nosymfollow",
+    "bind",
+    "rbind",
+    "move",
+    "shared",
+    "rshared",
+    "slave",
+    "rslave",
+    "private",
+    "rprivate",
+    "unbindable",
+    "runbindable",
+    "comment",
+    "loop",
+    "offset",
+    "sizelimit",
+    "encryption",
+    "helper",
+    "uhelper",
+];
+
+/// Pairs of generic flags that switch each other off, when the negation
+/// is not simply the name with a `no` prefix
+const FLAG_PAIRS: &[(&str, &str)] = &[("rw", "ro"), ("sync", "async"), ("loud", "silent")];
+
+/// Returns the option that switches the flag `name` the other way
+///
+/// Generic pairs like `rw`/`ro` are known; for anything else the negation
+/// adds or strips a `no` prefix (`acl`/`noacl`). `nofail` and `_netdev`
+/// have no negation, and an empty string is returned for them.
+pub(crate) fn negation(name: &str) -> String {
+    for &(on, off) in FLAG_PAIRS {
+        if name == on {
+            return off.to_owned();
+        }
+        if name == off {
+            return on.to_owned();
+        }
+    }
+    match name {
+        "nofail" | "_netdev" => String::new(),
+        _ if name.starts_with("no") => name[2..].to_owned(),
+        _ => format!("no{}", name),
+    }
+}
+
+/// What a mount option applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OptionKind {
+    /// A filesystem independent option from mount(8), like `ro` or `noauto`
+    Generic,
+    /// An option for userspace tools only (`x-*` and `X-*`)
+    UserSpace,
+    /// Anything else, passed on to the filesystem
+    Filesystem,
+}
+
+/// A single mount option, either a flag (`noatime`) or a `key=value` pair
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MountOption {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl MountOption {
+    /// Create a flag option
+    pub fn new(name: &str) -> MountOption {
+        MountOption {
+            name: name.to_owned(),
+            value: None,
+        }
+    }
+
+    /// Create a `key=value` option
+    pub fn with_value(name: &str, value: &str) -> MountOption {
+        MountOption {
+            name: name.to_owned(),
+            value: Some(value.to_owned()),
+        }
+    }
+
+    /// What the option applies to
+    pub fn kind(&self) -> OptionKind {
+        if self.name.starts_with("x-") || self.name.starts_with("X-") {
+            OptionKind::UserSpace
+        } else if GENERIC_OPTIONS.contains(&self.name.as_str()) {
+            OptionKind::Generic
+        } else {
+            OptionKind::Filesystem
+        }
+    }
+
+    /// Returns `true` if the option is a filesystem independent option from mount(8)
+    pub fn is_generic(&self) -> bool {
+        self.kind() == OptionKind::Generic
+    }
+
+    /// Returns `true` if the option is only meant for userspace tools (`x-*` and `X-*`)
+    pub fn is_user_space(&self) -> bool {
+        self.kind() == OptionKind::UserSpace
+    }
+}
+
+impl<'a> From<&'a str> for MountOption {
+    /// Split `key=value` at the first `=`; anything else is a flag
+    fn from(s: &'a str) -> MountOption {
+        match s.find('=') {
+            Some(i) => MountOption::with_value(&s[..i], &s[i + 1..]),
+            None => MountOption::new(s),
+        }
+    }
+}
+
+impl fmt::Display for MountOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            Some(ref v) => write!(f, "{}={}", self.name, v),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// The mount options of an entry (fs_mntops), in the order they were written
+///
+/// When an option is given more than once, the last one wins, as with mount(8).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct MountOptions {
+    options: Vec<MountOption>,
+}
+
+impl MountOptions {
+    /// Create an empty list of options
+    pub fn new() -> MountOptions {
+        MountOptions::default()
+    }
+
+    /// Iterate over the options in order
+    pub fn iter(&self) -> slice::Iter<'_, MountOption> {
+        self.options.iter()
+    }
+
+    /// Number of options
+    pub fn len(&self) -> usize {
+        self.options.len()
+    }
+
+    /// Returns `true` if there are no options
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    /// Returns `true` if an option called `name` is present, with or without a value
+    pub fn contains(&self, name: &str) -> bool {
+        self.options.iter().any(|o| o.name == name)
+    }
+
+    /// The last option called `name`
+    pub fn option(&self, name: &str) -> Option<&MountOption> {
+        self.options.iter().rev().find(|o| o.name == name)
+    }
+
+    /// The value of the last option called `name`
+    ///
+    /// Returns `None` if the option is absent or has no value.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.option(name).and_then(|o| o.value.as_deref())
+    }
+
+    /// The state of the flag `name`, taking its negation into account
+    ///
+    /// `flag("exec")` is `Some(false)` for `exec,noexec`, `Some(true)` for
+    /// `noexec,exec` and `None` if neither is given. `flag("ro")` looks at
+    /// `rw` as well.
+    pub fn flag(&self, name: &str) -> Option<bool> {
+        let off = negation(name);
+        self.options
+            .iter()
+            .rev()
+            .filter(|o| o.value.is_none())
+            .find_map(|o| {
+                if o.name == name {
+                    Some(true)
+                } else if !off.is_empty() && o.name == off {
+                    Some(false)
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Append an option
+    pub fn push<O: Into<MountOption>>(&mut self, option: O) {
+        self.options.push(option.into());
+    }
+
+    /// Insert an option at `index`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len()`.
+    pub fn insert<O: Into<MountOption>>(&mut self, index: usize, option: O) {
+        self.options.insert(index, option.into());
+    }
+
+    /// Remove every option called `name`, returning how many were removed
+    pub fn remove(&mut self, name: &str) -> usize {
+        let len = self.options.len();
+        self.options.retain(|o| o.name != name);
+        len - self.options.len()
+    }
+
+    /// Replace the options called `name` with `option`
+    ///
+    /// The new option takes the place of the first one removed, so the order
+    /// of the other options is kept; it is appended if `name` was absent.
+    pub fn replace<O: Into<MountOption>>(&mut self, name: &str, option: O) {
+        self.replace_any(&[name], option.into());
+    }
+
+    /// Set the value of `name`, replacing any previous value
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.replace(name, MountOption::with_value(name, value));
+    }
+
+    /// Switch the flag `name` on or off, replacing both it and its negation
+    ///
+    /// `set_flag("exec", false)` turns `rw,exec,nosuid` into `rw,noexec,nosuid`.
+    pub fn set_flag(&mut self, name: &str, on: bool) {
+        let off = negation(name);
+        let option = if on || off.is_empty() {
+            MountOption::new(name)
+        } else {
+            MountOption::new(&off)
+        };
+        if off.is_empty() && !on {
+            self.remove(name);
+        } else {
+            self.replace_any(&[name, &off], option);
+        }
+    }
+
+    fn replace_any(&mut self, names: &[&str], option: MountOption) {
+        let pos = self
+            .options
+            .iter()
+            .position(|o| names.contains(&o.name.as_str()));
+        self.options.retain(|o| !names.contains(&o.name.as_str()));
+        match pos {
+            Some(pos) => self.options.insert(pos, option),
+            None => self.options.push(option),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for MountOptions {
+    /// Parse a comma separated list of options, skipping empty items
+    fn from(s: &'a str) -> MountOptions {
+        s.split(',')
+            .filter(|o| !o.is_empty())
+            .map(MountOption::from)
+            .collect()
+    }
+}
+
+impl FromIterator<MountOption> for MountOptions {
+    fn from_iter<I: IntoIterator<Item = MountOption>>(iter: I) -> MountOptions {
+        MountOptions {
+            options: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a MountOptions {
+    type Item = &'a MountOption;
+    type IntoIter = slice::Iter<'a, MountOption>;
+
+    fn into_iter(self) -> slice::Iter<'a, MountOption> {
+        self.options.iter()
+    }
+}
+
+impl IntoIterator for MountOptions {
+    type Item = MountOption;
+    type IntoIter = ::std::vec::IntoIter<MountOption>;
+
+    fn into_iter(self) -> ::std::vec::IntoIter<MountOption> {
+        self.options.into_iter()
+    }
+}
+
+impl fmt::Display for MountOptions {
+    /// Writes the options separated by commas
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, o) in self.options.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            o.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn mount_options_access() {
+    let options =
+        MountOptions::from("defaults,noexec,uid=1000,,x-systemd.automount,exec,ro,comment=a=b");
+    assert_eq!(options.len(), 7);
+    assert_eq!(options.get("uid"), Some("1000"));
+    assert_eq!(options.get("comment"), Some("a=b"));
+    assert_eq!(options.get("defaults"), None);
+    assert!(options.contains("defaults"));
+    assert_eq!(options.flag("exec"), Some(true));
+    assert_eq!(options.flag("noexec"), Some(false));
+    assert_eq!(options.flag("ro"), Some(true));
+    assert_eq!(options.flag("rw"), Some(false));
+    assert_eq!(options.flag("suid"), None);
+    assert_eq!(options.flag("nofail"), None);
+
+    let kinds = options.iter().map(MountOption::kind).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            OptionKind::Generic,
+            OptionKind::Generic,
+            OptionKind::Filesystem,
+            OptionKind::UserSpace,
+            OptionKind::Generic,
+            OptionKind::Generic,
+            OptionKind::Generic,
+        ]
+    );
+    assert!(MountOption::from("_netdev").is_generic());
+    assert!(MountOption::from("X-mount.mkdir=0755").is_user_space());
+    assert_eq!(
+        options.to_string(),
+        "defaults,noexec,uid=1000,x-systemd.automount,exec,ro,comment=a=b"
+    );
+}
+
+#[test]
+fn mount_options_modify() {
+    let mut options = MountOptions::from("rw,exec,uid=0,nosuid,uid=1");
+    options.set_flag("exec", false);
+    assert_eq!(options.to_string(), "rw,noexec,uid=0,nosuid,uid=1");
+    options.set_flag("ro", true);
+    assert_eq!(options.to_string(), "ro,noexec,uid=0,nosuid,uid=1");
+    options.set("uid", "1000");
+    assert_eq!(options.to_string(), "ro,noexec,uid=1000,nosuid");
+    options.set_flag("nofail", true);
+    options.insert(0, "defaults");
+    assert_eq!(
+        options.to_string(),
+        "defaults,ro,noexec,uid=1000,nosuid,nofail"
+    );
+    options.set_flag("nofail", false);
+    assert_eq!(options.remove("nosuid"), 1);
+    assert_eq!(options.remove("nosuid"), 0);
+    options.replace("uid", "gid=100");
+    options.push(MountOption::with_value("x-systemd.requires", "/var"));
+    assert_eq!(
+        options.to_string(),
+        "defaults,ro,noexec,gid=100,x-systemd.requires=/var"
+    );
+    assert_eq!(MountOptions::new().to_string(), "");
+}
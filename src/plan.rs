@@ -0,0 +1,204 @@
+use std::error;
+use std::fmt;
+
+use {normalize_dir, Fstab};
+
+/// An entry hidden by a later entry with the same mount point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shadowed {
+    /// Index of the hidden entry
+    pub index: usize,
+    /// Index of the entry mounted over it
+    pub by: usize,
+}
+
+/// Entries that depend on each other to be mounted, such as two bind mounts
+/// whose sources are under each other's mount point
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MountCycle {
+    /// Indices of the entries in the cycle, starting and ending with the same entry
+    pub entries: Vec<usize>,
+    /// Their mount points
+    pub dirs: Vec<String>,
+}
+
+impl fmt::Display for MountCycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mount order has a cycle: {}", self.dirs.join(" -> "))
+    }
+}
+
+impl error::Error for MountCycle {}
+
+/// The order to mount entries in, as batches of entry indices
+///
+/// Every entry of a batch only depends on entries of earlier batches, so
+/// the entries of one batch can be mounted in parallel. Indices within a
+/// batch are in table order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MountPlan {
+    batches: Vec<Vec<usize>>,
+    shadowed: Vec<Shadowed>,
+}
+
+impl MountPlan {
+    /// The batches to mount, one after the other
+    pub fn batches(&self) -> &[Vec<usize>] {
+        &self.batches
+    }
+
+    /// The entries to mount, one at a time
+    pub fn order(&self) -> Vec<usize> {
+        self.batches.iter().flatten().cloned().collect()
+    }
+
+    /// The batches to unmount, the reverse of the mount batches
+    pub fn unmount_batches(&self) -> Vec<Vec<usize>> {
+        self.batches.iter().rev().cloned().collect()
+    }
+
+    /// The entries to unmount, one at a time
+    pub fn unmount_order(&self) -> Vec<usize> {
+        self.batches.iter().rev().flatten().cloned().collect()
+    }
+
+    /// Entries that another entry mounts over
+    pub fn shadowed(&self) -> &[Shadowed] {
+        &self.shadowed
+    }
+}
+
+/// Plan the order to mount `entries` in
+///
+/// An entry is mounted after the entries mounted at the directories above
+/// its mount point, after earlier entries with the same mount point, and,
+/// for a `bind` or `rbind` mount, after the entries its source is under.
+/// Entries without an absolute mount point, like swap, are not in the plan.
+///
+/// Fails with the entries of a cycle if they depend on each other.
+pub fn plan(entries: &[Fstab]) -> Result<MountPlan, MountCycle> {
+    let dirs = entries
+        .iter()
+        .map(|e| normalize_dir(&e.dir))
+        .collect::<Vec<_>>();
+    let planned = (0..entries.len())
+        .filter(|&i| dirs[i].starts_with('/'))
+        .collect::<Vec<_>>();
+
+    let mut deps = vec![Vec::new(); entries.len()];
+    let mut shadowed = Vec::new();
+    for &j in &planned {
+        let source = match entries[j].device.path() {
+            Some(p)
+                if entries[j].options.contains("bind") || entries[j].options.contains("rbind") =>
+            {
+                Some(normalize_dir(p))
+            }
+            _ => None,
+        };
+        for &i in &planned {
+            if i == j {
+                continue;
+            }
+            let above = dirs[i] != dirs[j] && is_under(&dirs[j], &dirs[i]);
+            let stacked = dirs[i] == dirs[j] && i < j;
+            let holds_source = source.as_ref().map_or(false, |s| is_under(s, &dirs[i]));
+            if above || stacked || holds_source {
+                deps[j].push(i);
+            }
+        }
+        if let Some(&by) = planned.iter().find(|&&k| k > j && dirs[k] == dirs[j]) {
+            shadowed.push(Shadowed { index: j, by });
+        }
+    }
+
+    let mut done = vec![false; entries.len()];
+    let mut remaining = planned;
+    let mut batches = Vec::new();
+    while !remaining.is_empty() {
+        let (batch, rest): (Vec<usize>, Vec<usize>) = remaining
+            .iter()
+            .partition(|&&j| deps[j].iter().all(|&i| done[i]));
+        if batch.is_empty() {
+            let cycle = find_cycle(&rest, &deps, &done);
+            let dirs = cycle.iter().map(|&i| entries[i].dir.clone()).collect();
+            return Err(MountCycle {
+                entries: cycle,
+                dirs,
+            });
+        }
+        for &j in &batch {
+            done[j] = true;
+        }
+        batches.push(batch);
+        remaining = rest;
+    }
+    Ok(MountPlan { batches, shadowed })
+}
+
+/// Returns `true` if `path` is `dir` or below it
+pub(crate) fn is_under(path: &str, dir: &str) -> bool {
+    dir == "/"
+        || path == dir
+        || (path.starts_with(dir) && path.as_bytes().get(dir.len()) == Some(&b'/'))
+}
+
+/// A cycle among entries that can not be mounted, starting and ending with the same entry
+fn find_cycle(stuck: &[usize], deps: &[Vec<usize>], done: &[bool]) -> Vec<usize> {
+    // Every stuck entry waits for another stuck entry, so following them must loop
+    let mut path = vec![stuck[0]];
+    loop {
+        let last = path[path.len() - 1];
+        let next = deps[last]
+            .iter()
+            .cloned()
+            .find(|&i| !done[i])
+            .expect("a stuck entry has a pending dependency");
+        if let Some(start) = path.iter().position(|&i| i == next) {
+            let mut cycle = path.split_off(start);
+            cycle.push(next);
+            return cycle;
+        }
+        path.push(next);
+    }
+}
+
+#[test]
+fn plan_mount_order() {
+    let entries = ::parse_fstab(concat!(
+        "/dev/sdb1 /var/log ext4 defaults 0 2\n",
+        "UUID=ffff none swap sw 0 0\n",
+        "/dev/sda1 / ext4 defaults 0 1\n",
+        "/srv/data /export/data none bind 0 0\n",
+        "/dev/sdc1 /var ext4 defaults 0 2\n",
+        "/dev/sdd1 /srv xfs defaults 0 2\n",
+        "tmpfs /tmp tmpfs defaults 0 0\n",
+        "/dev/sde1 /variable ext4 defaults 0 2\n",
+        "overlay /tmp overlay defaults 0 0\n",
+    ))
+    .unwrap();
+    let mounts = plan(&entries).unwrap();
+    assert_eq!(
+        mounts.batches(),
+        &[vec![2], vec![4, 5, 6, 7], vec![0, 3, 8]][..]
+    );
+    assert_eq!(mounts.order(), [2, 4, 5, 6, 7, 0, 3, 8]);
+    assert_eq!(
+        mounts.unmount_batches(),
+        [vec![0, 3, 8], vec![4, 5, 6, 7], vec![2]]
+    );
+    assert_eq!(mounts.unmount_order(), [0, 3, 8, 4, 5, 6, 7, 2]);
+    assert_eq!(mounts.shadowed(), &[Shadowed { index: 6, by: 8 }][..]);
+
+    let entries = ::parse_fstab(concat!(
+        "/dev/sda1 / ext4 defaults 0 1\n",
+        "/b/y /a none bind 0 0\n",
+        "/a/x /b none rbind 0 0\n",
+    ))
+    .unwrap();
+    let e = plan(&entries).unwrap_err();
+    assert_eq!(e.entries, [1, 2, 1]);
+    assert_eq!(e.to_string(), "mount order has a cycle: /a -> /b -> /a");
+    assert!(plan(&[]).unwrap().batches().is_empty());
+}
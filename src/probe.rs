@@ -0,0 +1,277 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use {Error, Result};
+
+/// How much of a device is read: enough for the btrfs superblock at 64KiB
+const PROBE_SIZE: usize = 0x10000 + 0x1000;
+
+/// Page sizes a swap area may have been created with
+const SWAP_PAGE_SIZES: [usize; 5] = [4096, 8192, 16384, 32768, 65536];
+
+/// What was found in the superblock of a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Superblock {
+    /// The filesystem type, as used in fs_vfstype (`ext4`, `xfs`, `vfat`, `swap`, ...)
+    pub fs_type: String,
+    /// The UUID, formatted like blkid does
+    pub uuid: Option<String>,
+    /// The label
+    pub label: Option<String>,
+}
+
+/// Read the superblock of the device or image at `path`
+///
+/// ext2/3/4, XFS, btrfs, vfat and swap are recognized; anything else is `None`.
+pub fn probe<P: AsRef<Path>>(path: P) -> Result<Option<Superblock>> {
+    let path = path.as_ref();
+    File::open(path)
+        .map_err(Error::from)
+        .and_then(probe_reader)
+        .map_err(|e| e.with_path(&path.to_string_lossy()))
+}
+
+/// Read the superblock from the start of a device or image
+pub fn probe_reader<R: Read>(reader: R) -> Result<Option<Superblock>> {
+    let mut buf = Vec::with_capacity(PROBE_SIZE);
+    reader.take(PROBE_SIZE as u64).read_to_end(&mut buf)?;
+    Ok(probe_ext(&buf)
+        .or_else(|| probe_xfs(&buf))
+        .or_else(|| probe_btrfs(&buf))
+        .or_else(|| probe_vfat(&buf))
+        .or_else(|| probe_swap(&buf)))
+}
+
+fn probe_ext(buf: &[u8]) -> Option<Superblock> {
+    let sb = buf.get(1024..2048)?;
+    if u16_le(&sb[0x38..]) != 0xEF53 {
+        return None;
+    }
+    let compat = u32_le(&sb[0x5C..]);
+    let incompat = u32_le(&sb[0x60..]);
+    let ro_compat = u32_le(&sb[0x64..]);
+    // Features introduced by ext4: extents, 64bit, mmp, flex_bg, ..., encrypt,
+    // casefold and huge_file, gdt_csum, dir_nlink, extra_isize, metadata_csum
+    let fs_type = if incompat & 0x0008 != 0 {
+        "jbd"
+    } else if incompat & 0x3FFC0 != 0 || ro_compat & 0x0478 != 0 {
+        "ext4"
+    } else if compat & 0x0004 != 0 {
+        "ext3"
+    } else {
+        "ext2"
+    };
+    Some(Superblock {
+        fs_type: fs_type.to_owned(),
+        uuid: uuid(&sb[0x68..0x78]),
+        label: label(&sb[0x78..0x88]),
+    })
+}
+
+fn probe_xfs(buf: &[u8]) -> Option<Superblock> {
+    let sb = buf.get(..120)?;
+    if &sb[..4] != b"XFSB" {
+        return None;
+    }
+    Some(Superblock {
+        fs_type: "xfs".to_owned(),
+        uuid: uuid(&sb[32..48]),
+        label: label(&sb[108..120]),
+    })
+}
+
+fn probe_btrfs(buf: &[u8]) -> Option<Superblock> {
+    let sb = buf.get(0x10000..0x10000 + 0x22b)?;
+    if &sb[0x40..0x48] != b"_BHRfS_M" {
+        return None;
+    }
+    Some(Superblock {
+        fs_type: "btrfs".to_owned(),
+        uuid: uuid(&sb[0x20..0x30]),
+        label: label(&sb[0x12b..0x22b]),
+    })
+}
+
+fn probe_vfat(buf: &[u8]) -> Option<Superblock> {
+    let bs = buf.get(..512)?;
+    if bs[510..512] != [0x55, 0xAA] || (bs[0] != 0xEB && bs[0] != 0xE9) {
+        return None;
+    }
+    // FAT32 keeps the volume ID and label further into the boot sector
+    let (id, name) = if &bs[0x52..0x57] == b"FAT32" {
+        (0x43, 0x47)
+    } else if &bs[0x36..0x39] == b"FAT" {
+        (0x27, 0x2B)
+    } else {
+        return None;
+    };
+    let id = u32_le(&bs[id..]);
+    let name = label(&bs[name..name + 11])
+        .map(|l| l.trim_end().to_owned())
+        .filter(|l| !l.is_empty() && l != "NO NAME");
+    Some(Superblock {
+        fs_type: "vfat".to_owned(),
+        uuid: if id == 0 {
+            None
+        } else {
+            Some(format!("{:04X}-{:04X}", id >> 16, id & 0xFFFF))
+        },
+        label: name,
+    })
+}
+
+fn probe_swap(buf: &[u8]) -> Option<Superblock> {
+    SWAP_PAGE_SIZES
+        .iter()
+        .find(|&&size| buf.get(size - 10..size) == Some(&b"SWAPSPACE2"[..]))?;
+    // The header follows the 1024 bytes reserved for boot loaders
+    let header = buf.get(1024..1024 + 44)?;
+    Some(Superblock {
+        fs_type: "swap".to_owned(),
+        uuid: uuid(&header[12..28]),
+        label: label(&header[28..44]),
+    })
+}
+
+fn u16_le(b: &[u8]) -> u16 {
+    u16::from(b[0]) | u16::from(b[1]) << 8
+}
+
+fn u32_le(b: &[u8]) -> u32 {
+    u32::from(u16_le(b)) | u32::from(u16_le(&b[2..])) << 16
+}
+
+/// Format a 16 byte UUID as `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`, or `None` if it is all zero
+fn uuid(b: &[u8]) -> Option<String> {
+    if b.iter().all(|&x| x == 0) {
+        return None;
+    }
+    let hex = b.iter().map(|x| format!("{:02x}", x)).collect::<String>();
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+/// A NUL terminated label, or `None` if it is empty
+fn label(b: &[u8]) -> Option<String> {
+    let end = b.iter().position(|&x| x == 0).unwrap_or(b.len());
+    if end == 0 {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&b[..end]).into_owned())
+}
+
+#[cfg(test)]
+const TEST_UUID: [u8; 16] = [
+    0x5b, 0x2e, 0x1c, 0x2a, 0x3d, 0x4e, 0x45, 0x1f, 0x9a, 0x0b, 0x1c, 0x2d, 0x3e, 0x4f, 0x50, 0x61,
+];
+
+/// An ext4 image with the test UUID and the label `root`
+#[cfg(test)]
+pub(crate) fn ext4_image() -> Vec<u8> {
+    let mut img = vec![0u8; 4096];
+    img[1024 + 0x38..1024 + 0x3A].copy_from_slice(&[0x53, 0xEF]);
+    img[1024 + 0x5C] = 0x04;
+    img[1024 + 0x60] = 0x40;
+    img[1024 + 0x68..1024 + 0x78].copy_from_slice(&TEST_UUID);
+    img[1024 + 0x78..1024 + 0x7C].copy_from_slice(b"root");
+    img
+}
+
+#[test]
+fn probe_filesystems() {
+    let uuid = Some("5b2e1c2a-3d4e-451f-9a0b-1c2d3e4f5061".to_owned());
+
+    let mut img = ext4_image();
+    assert_eq!(
+        probe_reader(&img[..]).unwrap().unwrap(),
+        Superblock {
+            fs_type: "ext4".to_owned(),
+            uuid: uuid.clone(),
+            label: Some("root".to_owned()),
+        }
+    );
+    img[1024 + 0x60] = 0;
+    assert_eq!(probe_reader(&img[..]).unwrap().unwrap().fs_type, "ext3");
+    // casefold, and encrypt, are only in ext4
+    img[1024 + 0x62] = 0x02;
+    assert_eq!(probe_reader(&img[..]).unwrap().unwrap().fs_type, "ext4");
+    img[1024 + 0x62] = 0x01;
+    assert_eq!(probe_reader(&img[..]).unwrap().unwrap().fs_type, "ext4");
+    img[1024 + 0x62] = 0;
+    img[1024 + 0x5C] = 0;
+    assert_eq!(probe_reader(&img[..]).unwrap().unwrap().fs_type, "ext2");
+
+    let mut img = vec![0u8; 4096];
+    img[..4].copy_from_slice(b"XFSB");
+    img[32..48].copy_from_slice(&TEST_UUID);
+    img[108..112].copy_from_slice(b"data");
+    let sb = probe_reader(&img[..]).unwrap().unwrap();
+    assert_eq!(
+        (sb.fs_type.as_str(), &sb.uuid, sb.label.as_deref()),
+        ("xfs", &uuid, Some("data"))
+    );
+
+    let mut img = vec![0u8; 0x11000];
+    img[0x10020..0x10030].copy_from_slice(&TEST_UUID);
+    img[0x10040..0x10048].copy_from_slice(b"_BHRfS_M");
+    img[0x1012b..0x10131].copy_from_slice(b"pool 1");
+    let sb = probe_reader(&img[..]).unwrap().unwrap();
+    assert_eq!(
+        (sb.fs_type.as_str(), &sb.uuid, sb.label.as_deref()),
+        ("btrfs", &uuid, Some("pool 1"))
+    );
+
+    let mut img = vec![0u8; 512];
+    img[0] = 0xEB;
+    img[510..512].copy_from_slice(&[0x55, 0xAA]);
+    img[0x52..0x5A].copy_from_slice(b"FAT32   ");
+    img[0x43..0x47].copy_from_slice(&[0xC0, 0x3A, 0xC1, 0xF1]);
+    img[0x47..0x52].copy_from_slice(b"EFI        ");
+    let sb = probe_reader(&img[..]).unwrap().unwrap();
+    assert_eq!(sb.fs_type, "vfat");
+    assert_eq!(sb.uuid.as_deref(), Some("F1C1-3AC0"));
+    assert_eq!(sb.label.as_deref(), Some("EFI"));
+
+    let mut img = vec![0u8; 512];
+    img[0] = 0xEB;
+    img[510..512].copy_from_slice(&[0x55, 0xAA]);
+    img[0x36..0x3E].copy_from_slice(b"FAT16   ");
+    img[0x27..0x2B].copy_from_slice(&[0x34, 0x12, 0xCD, 0xAB]);
+    img[0x2B..0x36].copy_from_slice(b"NO NAME    ");
+    let sb = probe_reader(&img[..]).unwrap().unwrap();
+    assert_eq!(sb.uuid.as_deref(), Some("ABCD-1234"));
+    assert_eq!(sb.label, None);
+
+    let mut img = vec![0u8; 8192];
+    img[8192 - 10..].copy_from_slice(b"SWAPSPACE2");
+    img[1036..1052].copy_from_slice(&TEST_UUID);
+    let sb = probe_reader(&img[..]).unwrap().unwrap();
+    assert_eq!(
+        (sb.fs_type.as_str(), &sb.uuid, sb.label),
+        ("swap", &uuid, None)
+    );
+
+    assert_eq!(probe_reader(&[0u8; 8192][..]).unwrap(), None);
+    assert_eq!(probe_reader(&[][..]).unwrap(), None);
+}
+
+#[test]
+fn probe_image_file() {
+    let dir = ::test_dir("probe");
+    let path = dir.join("ext4.img");
+    ::std::fs::write(&path, ext4_image()).unwrap();
+    assert_eq!(
+        probe(&path).unwrap().unwrap().label.as_deref(),
+        Some("root")
+    );
+    let e = probe(dir.join("missing.img")).unwrap_err();
+    assert_eq!(e.path(), Some(dir.join("missing.img").to_str().unwrap()));
+    ::std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,320 @@
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use probe::probe;
+use Device;
+
+/// How many symlinks are followed before giving up
+const MAX_LINKS: usize = 40;
+
+/// Why a device could not be resolved to a device node
+///
+/// With the `serde` feature, it is serialized like `ErrorType`, as
+/// `{"kind": "not_found", "detail": "UUID=0a1b2c3d"}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "kind", content = "detail", rename_all = "snake_case")
+)]
+pub enum ResolveError {
+    /// No device node was found for the device
+    NotFound(Device),
+    /// The device is a network or pseudo source, which has no device node
+    Unresolvable(Device),
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResolveError::NotFound(ref d) => write!(f, "device not found: {}", d),
+            ResolveError::Unresolvable(ref d) => write!(f, "device has no device node: {}", d),
+        }
+    }
+}
+
+impl error::Error for ResolveError {}
+
+/// Resolves devices to device nodes through the `/dev/disk/by-*` symlinks
+/// maintained by udev
+///
+/// All lookups happen below a root directory, `/` by default, so a resolver
+/// can be pointed at a copy of a system tree. Returned paths are as seen
+/// from that root, e.g. `/dev/sda1`.
+///
+/// When there is no symlink for a `UUID=` or `LABEL=` tag, as in early boot
+/// or in containers, the superblocks of the block devices are read instead.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    root: PathBuf,
+    probing: bool,
+    candidates: Option<Vec<PathBuf>>,
+}
+
+impl Default for Resolver {
+    fn default() -> Resolver {
+        Resolver::new()
+    }
+}
+
+impl Resolver {
+    /// Create a resolver for the running system
+    pub fn new() -> Resolver {
+        Resolver::with_root("/")
+    }
+
+    /// Create a resolver that looks up everything below `root`
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> Resolver {
+        Resolver {
+            root: root.into(),
+            probing: true,
+            candidates: None,
+        }
+    }
+
+    /// Enable or disable reading superblocks when a tag has no symlink
+    pub fn with_probing(mut self, probing: bool) -> Resolver {
+        self.probing = probing;
+        self
+    }
+
+    /// Probe these devices instead of the ones listed in `/proc/partitions`
+    ///
+    /// The paths are looked up below the root, like everything else.
+    pub fn with_candidates<I, P>(mut self, devices: I) -> Resolver
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.candidates = Some(devices.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// The root directory lookups happen in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve `device` to the canonical path of its device node
+    ///
+    /// Tags are looked up in `/dev/disk/by-uuid`, `by-label`, `by-partuuid`,
+    /// `by-partlabel` and `by-id`; paths are followed if they are symlinks.
+    /// If a `UUID=` or `LABEL=` has no symlink, the candidate devices are
+    /// probed for a matching superblock. Network and pseudo sources have no
+    /// device node and can not be resolved.
+    pub fn resolve(&self, device: &Device) -> Result<PathBuf, ResolveError> {
+        let link = match *device {
+            Device::Uuid(ref v) => by_tag("by-uuid", v),
+            Device::Label(ref v) => by_tag("by-label", v),
+            Device::PartUuid(ref v) => by_tag("by-partuuid", v),
+            Device::PartLabel(ref v) => by_tag("by-partlabel", v),
+            Device::Id(ref v) => by_tag("by-id", v),
+            Device::MountPoint(ref p) => PathBuf::from(p),
+            _ => return Err(ResolveError::Unresolvable(device.clone())),
+        };
+        self.follow(&link)
+            .or_else(|| self.probe_tag(device))
+            .ok_or_else(|| ResolveError::NotFound(device.clone()))
+    }
+
+    /// Find the candidate device whose superblock carries the tag
+    fn probe_tag(&self, device: &Device) -> Option<PathBuf> {
+        match *device {
+            Device::Uuid(_) | Device::Label(_) if self.probing => {}
+            _ => return None,
+        }
+        let matches = |path: &PathBuf| {
+            let sb = match probe(self.host_path(path)) {
+                Ok(Some(sb)) => sb,
+                _ => return false,
+            };
+            match *device {
+                Device::Uuid(ref v) => sb.uuid.map_or(false, |u| u.eq_ignore_ascii_case(v)),
+                Device::Label(ref v) => sb.label.as_ref() == Some(v),
+                _ => false,
+            }
+        };
+        self.candidates()
+            .into_iter()
+            .map(|p| normalize(&p))
+            .find(matches)
+    }
+
+    /// The devices to probe: the configured ones, or the partitions the kernel knows
+    fn candidates(&self) -> Vec<PathBuf> {
+        if let Some(ref candidates) = self.candidates {
+            return candidates.clone();
+        }
+        let partitions = self.host_path(Path::new("/proc/partitions"));
+        let content = match fs::read_to_string(partitions) {
+            Ok(content) => content,
+            Err(_) => return Vec::new(),
+        };
+        // major minor #blocks name, after a header line
+        content
+            .lines()
+            .filter_map(|line| {
+                let fields = line.split_whitespace().collect::<Vec<_>>();
+                match fields[..] {
+                    [major, _, _, name] if major.parse::<u32>().is_ok() => {
+                        Some(Path::new("/dev").join(name))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// The location of `path` below the root
+    pub(crate) fn host_path(&self, path: &Path) -> PathBuf {
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    /// Follow the symlinks of `path`, returning the final path if it exists
+    fn follow(&self, path: &Path) -> Option<PathBuf> {
+        let mut path = normalize(path);
+        for _ in 0..MAX_LINKS {
+            let meta = fs::symlink_metadata(self.host_path(&path)).ok()?;
+            if !meta.file_type().is_symlink() {
+                return Some(path);
+            }
+            let target = fs::read_link(self.host_path(&path)).ok()?;
+            path = match path.parent() {
+                Some(parent) => normalize(&parent.join(target)),
+                None => normalize(&target),
+            };
+        }
+        None
+    }
+}
+
+/// The udev symlink for a tag value
+fn by_tag(dir: &str, value: &str) -> PathBuf {
+    Path::new("/dev/disk").join(dir).join(encode(value))
+}
+
+/// Encode a tag value the way udev names its symlinks
+///
+/// Characters other than ASCII alphanumerics, `#+-.:=@_` and non-ASCII
+/// UTF-8 are written as `\xNN`, so `My Disk` becomes `My\x20Disk`.
+fn encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_ascii_alphanumeric() || "#+-.:=@_".contains(c) || !c.is_ascii() {
+            out.push(c);
+        } else {
+            out.push_str(&format!("\\x{:02x}", c as u32));
+        }
+    }
+    out
+}
+
+/// Resolve `.` and `..` without touching the filesystem, keeping the path absolute
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::from("/");
+    for c in path.components() {
+        match c {
+            Component::Normal(part) => out.push(part),
+            Component::ParentDir => {
+                out.pop();
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[test]
+fn resolve_tags() {
+    use std::os::unix::fs::symlink;
+
+    let root = ::test_dir("resolve");
+    let disk = root.join("dev/disk");
+    for dir in &[
+        "by-uuid",
+        "by-label",
+        "by-partuuid",
+        "by-partlabel",
+        "by-id",
+    ] {
+        fs::create_dir_all(disk.join(dir)).unwrap();
+    }
+    fs::create_dir_all(root.join("dev/mapper")).unwrap();
+    fs::write(root.join("dev/sda1"), b"").unwrap();
+    fs::write(root.join("dev/dm-0"), b"").unwrap();
+    symlink("../../sda1", disk.join("by-uuid/0a1b2c3d")).unwrap();
+    symlink("../../sda1", disk.join("by-label/My\\x20Disk")).unwrap();
+    symlink("/dev/sda1", disk.join("by-partuuid/8c2a7f1e-01")).unwrap();
+    symlink("../../mapper/root", disk.join("by-partlabel/root")).unwrap();
+    symlink("../dm-0", root.join("dev/mapper/root")).unwrap();
+    symlink("../../sdz9", disk.join("by-id/ata-gone")).unwrap();
+
+    let resolver = Resolver::with_root(&root);
+    let sda1 = PathBuf::from("/dev/sda1");
+    let resolve = |d: &str| resolver.resolve(&d.parse::<Device>().unwrap());
+    assert_eq!(resolve("UUID=0a1b2c3d").unwrap(), sda1);
+    assert_eq!(resolve("LABEL=\"My Disk\"").unwrap(), sda1);
+    assert_eq!(resolve("PARTUUID=8c2a7f1e-01").unwrap(), sda1);
+    assert_eq!(
+        resolve("PARTLABEL=root").unwrap(),
+        PathBuf::from("/dev/dm-0")
+    );
+    assert_eq!(
+        resolve("/dev/mapper/root").unwrap(),
+        PathBuf::from("/dev/dm-0")
+    );
+    assert_eq!(resolve("/dev/../dev/sda1").unwrap(), sda1);
+
+    let e = resolve("ID=ata-gone").unwrap_err();
+    assert_eq!(e.to_string(), "device not found: ID=ata-gone");
+    assert_eq!(
+        resolve("UUID=ffff").unwrap_err(),
+        ResolveError::NotFound(Device::Uuid("ffff".to_owned()))
+    );
+    assert!(resolve("/dev/sdb1").is_err());
+    let e = resolve("server:/export").unwrap_err();
+    assert_eq!(e.to_string(), "device has no device node: server:/export");
+    assert!(resolve("tmpfs").is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn resolve_by_probing() {
+    let root = ::test_dir("resolve-probe");
+    fs::create_dir_all(root.join("proc")).unwrap();
+    fs::create_dir_all(root.join("dev")).unwrap();
+    fs::write(
+        root.join("proc/partitions"),
+        "major minor  #blocks  name\n\n   7        0       4 loop0\n   7        1       4 loop1\n",
+    )
+    .unwrap();
+    fs::write(root.join("dev/loop0"), vec![0u8; 4096]).unwrap();
+    fs::write(root.join("dev/loop1"), ::probe::ext4_image()).unwrap();
+
+    let resolver = Resolver::with_root(&root);
+    let resolve = |r: &Resolver, d: &str| r.resolve(&d.parse::<Device>().unwrap());
+    let loop1 = PathBuf::from("/dev/loop1");
+    assert_eq!(resolve(&resolver, "LABEL=root").unwrap(), loop1);
+    assert_eq!(
+        resolve(&resolver, "UUID=5B2E1C2A-3D4E-451F-9A0B-1C2D3E4F5061").unwrap(),
+        loop1
+    );
+    assert!(resolve(&resolver, "LABEL=home").is_err());
+    assert!(resolve(&resolver, "PARTLABEL=root").is_err());
+    assert!(resolve(&resolver.clone().with_probing(false), "LABEL=root").is_err());
+    let only_loop0 = resolver.with_candidates(vec!["/dev/loop0"]);
+    assert!(resolve(&only_loop0, "LABEL=root").is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn encode_tags() {
+    assert_eq!(encode("My Disk"), "My\\x20Disk");
+    assert_eq!(encode("a/b\\c"), "a\\x2fb\\x5cc");
+    assert_eq!(encode("F1C1-3AC0"), "F1C1-3AC0");
+    assert_eq!(encode("été"), "été");
+}
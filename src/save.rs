@@ -0,0 +1,338 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libc;
+
+use textdiff::unified_diff;
+use Result;
+
+/// Mode of a file that did not exist before
+const NEW_FILE_MODE: u32 = 0o644;
+
+/// How the file being replaced is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backup {
+    /// No backup is made
+    #[default]
+    None,
+    /// The file is copied to `<file>.bak`, replacing an older backup
+    Bak,
+    /// The file is copied to `<file>.<YYYYMMDD-HHMMSS>`, in UTC, or to
+    /// `<file>.<YYYYMMDD-HHMMSS>.<n>` if that backup already exists
+    Timestamped,
+}
+
+/// What saving a file did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Saved {
+    /// The file was replaced, and the old one copied to the backup path, if any
+    Written { backup: Option<PathBuf> },
+    /// Nothing was written; the unified diff of the change, empty if there is none
+    DryRun(String),
+}
+
+/// How a file is saved
+///
+/// The content is written to a temporary file in the same directory, synced
+/// to disk, given the mode and owner of the file it replaces, and renamed
+/// over it, so the file is never seen half-written. A symlink is followed
+/// and its target is replaced.
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    backup: Backup,
+    dry_run: bool,
+}
+
+impl SaveOptions {
+    /// Replace files without backup
+    pub fn new() -> SaveOptions {
+        SaveOptions::default()
+    }
+
+    /// Keep a backup of the file being replaced
+    pub fn with_backup(mut self, backup: Backup) -> SaveOptions {
+        self.backup = backup;
+        self
+    }
+
+    /// Only compute the diff of the change, without writing anything
+    pub fn with_dry_run(mut self, dry_run: bool) -> SaveOptions {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Replace the file at `path` with `content`
+    pub fn save(&self, path: &str, content: &str) -> Result<Saved> {
+        self.save_file(Path::new(path), content)
+            .map_err(|e| e.with_path(path))
+    }
+
+    fn save_file(&self, path: &Path, content: &str) -> Result<Saved> {
+        let path = match fs::canonicalize(path) {
+            Ok(target) => target,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => path.to_owned(),
+            Err(e) => return Err(e.into()),
+        };
+        let original = match fs::metadata(&path) {
+            Ok(meta) => Some(meta),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        if self.dry_run {
+            let old = match original {
+                Some(_) => fs::read_to_string(&path)?,
+                None => String::new(),
+            };
+            let label = path.to_string_lossy();
+            return Ok(Saved::DryRun(unified_diff(&old, content, &label, &label)));
+        }
+
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+            _ => PathBuf::from("."),
+        };
+        let (tmp_path, mut tmp) = create_temp(&dir, &path)?;
+        let written = write_temp(&mut tmp, content, original.as_ref());
+        drop(tmp);
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+
+        let backup = match original {
+            Some(ref meta) => self.backup(&path, meta),
+            None => Ok(None),
+        };
+        let backup = match backup {
+            Ok(backup) => backup,
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(e);
+            }
+        };
+        if let Err(e) = fs::rename(&tmp_path, &path) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e.into());
+        }
+        File::open(&dir)?.sync_all()?;
+        Ok(Saved::Written { backup })
+    }
+
+    /// Copy the file at `path` to its backup, returning the backup path
+    fn backup(&self, path: &Path, original: &fs::Metadata) -> Result<Option<PathBuf>> {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => return Ok(None),
+        };
+        match self.backup {
+            Backup::None => Ok(None),
+            Backup::Bak => {
+                let backup = path.with_file_name(format!("{}.bak", name));
+                fs::copy(path, &backup)?;
+                // The backup must be on disk before the file is replaced
+                File::open(&backup)?.sync_all()?;
+                Ok(Some(backup))
+            }
+            Backup::Timestamped => {
+                let mut source = File::open(path)?;
+                let stamp = timestamp(SystemTime::now());
+                let (backup, mut file) = create_new(|n| match n {
+                    0 => path.with_file_name(format!("{}.{}", name, stamp)),
+                    n => path.with_file_name(format!("{}.{}.{}", name, stamp, n)),
+                })?;
+                let copied = io::copy(&mut source, &mut file)
+                    .and_then(|_| file.set_permissions(original.permissions()))
+                    .and_then(|_| file.sync_all());
+                if let Err(e) = copied {
+                    let _ = fs::remove_file(&backup);
+                    return Err(e.into());
+                }
+                Ok(Some(backup))
+            }
+        }
+    }
+}
+
+/// Create the first of the paths `path(0)`, `path(1)`, ... that does not exist yet
+fn create_new<F: Fn(u32) -> PathBuf>(path: F) -> Result<(PathBuf, File)> {
+    let mut n = 0;
+    loop {
+        let path = path(n);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Create a new, empty temporary file next to `path`
+fn create_temp(dir: &Path, path: &Path) -> Result<(PathBuf, File)> {
+    let name = path
+        .file_name()
+        .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+    create_new(|n| dir.join(format!(".{}.{}.{}.tmp", name, process::id(), n)))
+}
+
+/// Write the content to the temporary file and give it the mode and owner of the original
+fn write_temp(tmp: &mut File, content: &str, original: Option<&fs::Metadata>) -> Result<()> {
+    tmp.write_all(content.as_bytes())?;
+    let mode = original.map_or(NEW_FILE_MODE, |m| m.mode() & 0o7777);
+    if let Some(meta) = original {
+        let own = tmp.metadata()?;
+        if (own.uid(), own.gid()) != (meta.uid(), meta.gid())
+            && unsafe { libc::fchown(tmp.as_raw_fd(), meta.uid(), meta.gid()) } != 0
+        {
+            return Err(io::Error::last_os_error().into());
+        }
+    }
+    tmp.set_permissions(fs::Permissions::from_mode(mode))?;
+    tmp.sync_all()?;
+    Ok(())
+}
+
+/// `YYYYMMDD-HHMMSS` in UTC
+fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // Civil date from days since 1970-01-01, after Howard Hinnant's civil_from_days
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[test]
+fn save_atomically() {
+    let dir = ::test_dir("save");
+    let path = dir.join("fstab");
+    let path_str = path.to_str().unwrap();
+    fs::write(&path, "proc /proc proc defaults 0 0\n").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+    let content = "proc /proc proc defaults 0 0\ntmpfs /tmp tmpfs defaults 0 0\n";
+    let saved = SaveOptions::new()
+        .with_dry_run(true)
+        .save(path_str, content)
+        .unwrap();
+    assert_eq!(
+        saved,
+        Saved::DryRun(format!(
+            "--- {0}\n+++ {0}\n@@ -1 +1,2 @@\n proc /proc proc defaults 0 0\n+tmpfs /tmp tmpfs defaults 0 0\n",
+            path_str
+        ))
+    );
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "proc /proc proc defaults 0 0\n"
+    );
+
+    let saved = SaveOptions::new()
+        .with_backup(Backup::Bak)
+        .save(path_str, content)
+        .unwrap();
+    let bak = dir.join("fstab.bak");
+    assert_eq!(
+        saved,
+        Saved::Written {
+            backup: Some(bak.clone())
+        }
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), content);
+    assert_eq!(
+        fs::read_to_string(&bak).unwrap(),
+        "proc /proc proc defaults 0 0\n"
+    );
+    assert_eq!(fs::metadata(&path).unwrap().mode() & 0o777, 0o640);
+
+    // A symlink is kept, and its target replaced
+    let link = dir.join("link");
+    ::std::os::unix::fs::symlink("fstab", &link).unwrap();
+    let saved = SaveOptions::new()
+        .with_backup(Backup::Timestamped)
+        .save(link.to_str().unwrap(), "")
+        .unwrap();
+    let first = match saved {
+        Saved::Written { backup: Some(b) } => b,
+        other => panic!("unexpected {:?}", other),
+    };
+    assert!(first
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("fstab.20"));
+    assert_eq!(fs::read_to_string(&first).unwrap(), content);
+    assert_eq!(fs::metadata(&first).unwrap().mode() & 0o777, 0o640);
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "");
+
+    // A second backup in the same second does not replace the first
+    let saved = SaveOptions::new()
+        .with_backup(Backup::Timestamped)
+        .save(path_str, "x\n")
+        .unwrap();
+    match saved {
+        Saved::Written { backup: Some(b) } => {
+            assert_ne!(b, first);
+            assert_eq!(fs::read_to_string(&b).unwrap(), "");
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(fs::read_to_string(&first).unwrap(), content);
+
+    let new = dir.join("new");
+    let saved = SaveOptions::new()
+        .with_backup(Backup::Bak)
+        .save(new.to_str().unwrap(), "x\n")
+        .unwrap();
+    assert_eq!(saved, Saved::Written { backup: None });
+    assert_eq!(fs::metadata(&new).unwrap().mode() & 0o777, 0o644);
+
+    // No temporary file is left behind
+    assert!(fs::read_dir(&dir)
+        .unwrap()
+        .all(|e| !e.unwrap().file_name().to_string_lossy().ends_with(".tmp")));
+
+    let e = SaveOptions::new()
+        .save(dir.join("missing/fstab").to_str().unwrap(), "")
+        .unwrap_err();
+    assert!(e.path().unwrap().ends_with("missing/fstab"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn backup_timestamps() {
+    use std::time::Duration;
+    assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000");
+    let t = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+    assert_eq!(timestamp(t), "20240229-123456");
+}
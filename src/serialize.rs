@@ -0,0 +1,262 @@
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use {Device, MountOption, MountOptions};
+
+impl Serialize for Device {
+    /// Serialize the device as a string in `fs_spec` form
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Device {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Device, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.parse().unwrap_or_else(|e| match e {}))
+    }
+}
+
+impl Serialize for MountOption {
+    /// Serialize the option as `name` or `name=value`
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MountOption {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MountOption, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        if s.is_empty() {
+            return Err(de::Error::invalid_value(
+                de::Unexpected::Str(&s),
+                &"a mount option",
+            ));
+        }
+        Ok(MountOption::from(s.as_str()))
+    }
+}
+
+impl Serialize for MountOptions {
+    /// Serialize the options as a list of strings
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MountOptions {
+    /// Deserialize a list of options, or a string of comma separated options
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MountOptions, D::Error> {
+        deserializer.deserialize_any(OptionsVisitor)
+    }
+}
+
+struct OptionsVisitor;
+
+impl<'de> Visitor<'de> for OptionsVisitor {
+    type Value = MountOptions;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of mount options or a comma separated string")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<MountOptions, E> {
+        Ok(MountOptions::from(s))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<MountOptions, A::Error> {
+        let mut options = MountOptions::new();
+        while let Some(option) = seq.next_element::<MountOption>()? {
+            options.push(option);
+        }
+        Ok(options)
+    }
+}
+
+/// (De)serialize an I/O error as its message, for fields with
+/// `#[serde(with = "::serialize::io_message")]`
+#[cfg(feature = "mount")]
+pub(crate) mod io_message {
+    use std::io;
+    use std::sync::Arc;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(e: &Arc<io::Error>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(e)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<io::Error>, D::Error> {
+        let message = String::deserialize(deserializer)?;
+        Ok(Arc::new(io::Error::new(io::ErrorKind::Other, message)))
+    }
+}
+
+#[cfg(test)]
+extern crate serde_json;
+#[cfg(test)]
+extern crate toml;
+
+#[test]
+fn json_round_trip() {
+    let text = concat!(
+        "UUID=0a1b2c3d\t/\text4\terrors=remount-ro\t0\t1\n",
+        "LABEL=My\\040Disk\t/mnt/my\\040disk\tvfat\tuid=1000,noauto\t1\t2\n",
+        "[fe80::1]:/srv\t/mnt/nfs\tnfs\tdefaults\t0\t0\n",
+        "tmpfs\t/tmp\ttmpfs\tdefaults\t0\t0\n",
+    );
+    let entries = ::parse_fstab(text).unwrap();
+    let json = serde_json::to_string(&entries).unwrap();
+    assert!(json.starts_with(
+        "[{\"device\":\"UUID=0a1b2c3d\",\"dir\":\"/\",\"device_type\":\"ext4\",\
+         \"options\":[\"errors=remount-ro\"],\"dump\":false,\"fsck\":1},"
+    ));
+    assert!(json.contains("\"device\":\"LABEL=My Disk\",\"dir\":\"/mnt/my disk\""));
+    assert!(json.contains("\"device\":\"[fe80::1]:/srv\""));
+
+    let back = serde_json::from_str::<Vec<::Fstab>>(&json).unwrap();
+    assert_eq!(back, entries);
+    let written = back.iter().map(|e| format!("{}\n", e)).collect::<String>();
+    assert_eq!(written, text);
+
+    let declared = serde_json::from_str::<::Fstab>(
+        r#"{"device": "/dev/sdb1", "dir": "/data", "device_type": "xfs",
+            "options": "noatime,logbufs=8"}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        declared.to_string(),
+        "/dev/sdb1\t/data\txfs\tnoatime,logbufs=8\t0\t0"
+    );
+    let minimal = serde_json::from_str::<::Fstab>(
+        r#"{"device": "proc", "dir": "/proc", "device_type": "proc"}"#,
+    )
+    .unwrap();
+    assert!(minimal.options.is_empty());
+    assert!(serde_json::from_str::<::Fstab>(r#"{"device": "proc"}"#).is_err());
+    assert!(serde_json::from_str::<MountOptions>(r#"["ro", ""]"#).is_err());
+}
+
+#[test]
+fn toml_round_trip() {
+    // A TOML document is a table, so the entries are an array of tables in it
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Table {
+        entries: Vec<::Fstab>,
+    }
+
+    let table = Table {
+        entries: ::parse_fstab(concat!(
+            "UUID=0a1b2c3d / ext4 errors=remount-ro 0 1\n",
+            "LABEL=My\\040Disk /mnt/my\\040disk vfat uid=1000,noauto 1 2\n",
+            "proc /proc proc defaults 0 0\n",
+        ))
+        .unwrap(),
+    };
+    let text = toml::to_string(&table).unwrap();
+    assert!(text.starts_with(
+        "[[entries]]\ndevice = \"UUID=0a1b2c3d\"\ndir = \"/\"\ndevice_type = \"ext4\"\n\
+         options = [\"errors=remount-ro\"]\ndump = false\nfsck = 1\n"
+    ));
+    assert!(text.contains("options = [\"uid=1000\", \"noauto\"]\n"));
+    assert_eq!(toml::from_str::<Table>(&text).unwrap(), table);
+
+    let declared = toml::from_str::<Table>(concat!(
+        "[[entries]]\n",
+        "device = \"/dev/sdb1\"\n",
+        "dir = \"/data\"\n",
+        "device_type = \"xfs\"\n",
+        "options = \"noatime,logbufs=8\"\n",
+        "\n",
+        "[[entries]]\n",
+        "device = \"LABEL=backup\"\n",
+        "dir = \"/backup\"\n",
+        "device_type = \"ext4\"\n",
+        "options = [\"noauto\", \"commit=60\"]\n",
+        "fsck = 2\n",
+    ))
+    .unwrap();
+    assert_eq!(
+        ::fstab_to_string(&declared.entries),
+        "/dev/sdb1    /data   xfs  noatime,logbufs=8 0 0\n\
+         LABEL=backup /backup ext4 noauto,commit=60  0 2\n"
+    );
+    assert!(toml::from_str::<Table>("[[entries]]\ndevice = \"proc\"\n").is_err());
+}
+
+#[test]
+fn json_errors() {
+    let e = ::parse_fstab("/dev/sda1 /mnt ext4 defaults x 0\n").unwrap_err();
+    let json = serde_json::to_value(&e).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "reason": {"kind": "num_parse_error", "detail": "invalid digit found in string"},
+            "line": 1,
+            "span": {"start": 29, "end": 30},
+            "text": "/dev/sda1 /mnt ext4 defaults x 0",
+        })
+    );
+    let back = serde_json::from_value::<::Error>(json).unwrap();
+    assert_eq!(back.to_string(), e.to_string());
+
+    let e = ::Error::new(::ErrorType::InvalidUtf8);
+    let json = serde_json::to_string(&e).unwrap();
+    assert_eq!(json, r#"{"reason":{"kind":"invalid_utf8"}}"#);
+    let back = serde_json::from_str::<::Error>(&json).unwrap();
+    assert_eq!(back.to_string(), "line is not valid UTF-8");
+}
+
+#[test]
+fn json_reports() {
+    let problem = ::Problem {
+        index: 2,
+        kind: ::ProblemKind::DeviceNotFound(Device::Uuid("ffff".to_owned())),
+    };
+    let json = serde_json::to_value(&problem).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"index": 2, "kind": {"kind": "device_not_found", "detail": "UUID=ffff"}})
+    );
+    assert_eq!(serde_json::from_value::<::Problem>(json).unwrap(), problem);
+
+    let lints = ::lint(&::parse_fstab("/dev/sda1 / ext4 defaults 0 1\n").unwrap());
+    let json = serde_json::to_string(&lints).unwrap();
+    assert!(json.starts_with(
+        r#"[{"index":0,"rule":"unstable-device-path","severity":"warning","message":"#
+    ));
+    assert_eq!(serde_json::from_str::<Vec<::Lint>>(&json).unwrap(), lints);
+
+    let e = ::ResolveError::Unresolvable(Device::Pseudo("tmpfs".to_owned()));
+    let json = serde_json::to_string(&e).unwrap();
+    assert_eq!(json, r#"{"kind":"unresolvable","detail":"tmpfs"}"#);
+    assert_eq!(serde_json::from_str::<::ResolveError>(&json).unwrap(), e);
+
+    let report = ::parse_fstab_lenient("/dev/sda1 /mnt\n");
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["diagnostics"][0]["severity"], "error");
+    let back = serde_json::from_value::<::ParseReport>(json).unwrap();
+    assert_eq!(
+        back.diagnostics[0].to_string(),
+        report.diagnostics[0].to_string()
+    );
+}
+
+#[cfg(feature = "mount")]
+#[test]
+fn json_mount_errors() {
+    use std::io;
+    use std::sync::Arc;
+
+    let e = ::MountError::Failed {
+        target: "/mnt".to_owned(),
+        error: Arc::new(io::Error::from_raw_os_error(2)),
+    };
+    let json = serde_json::to_string(&e).unwrap();
+    let back = serde_json::from_str::<::MountError>(&json).unwrap();
+    assert_eq!(back.to_string(), e.to_string());
+}
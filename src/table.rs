@@ -0,0 +1,313 @@
+use std::iter::FromIterator;
+use std::slice;
+
+use {normalize_dir, Device, Environment, Fstab, MountOptions};
+
+/// A list of entries with the lookups that are otherwise written as linear scans
+///
+/// Lookups return the first matching entry, in table order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FstabTable {
+    entries: Vec<Fstab>,
+}
+
+impl FstabTable {
+    /// Create a table of `entries`
+    pub fn new(entries: Vec<Fstab>) -> FstabTable {
+        FstabTable { entries }
+    }
+
+    /// The entries, in order
+    pub fn entries(&self) -> &[Fstab] {
+        &self.entries
+    }
+
+    /// Take the entries out of the table
+    pub fn into_entries(self) -> Vec<Fstab> {
+        self.entries
+    }
+
+    /// Iterate over the entries in order
+    pub fn iter(&self) -> slice::Iter<'_, Fstab> {
+        self.entries.iter()
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entry mounted at `dir`, comparing normalized paths so that
+    /// `/home/` finds `/home`
+    pub fn find_mount_point(&self, dir: &str) -> Option<&Fstab> {
+        let dir = normalize_dir(dir);
+        self.entries.iter().find(|e| normalize_dir(&e.dir) == dir)
+    }
+
+    /// The entry for `device`, which matches a tag by tag and a path by path
+    pub fn find_device(&self, device: &Device) -> Option<&Fstab> {
+        self.entries.iter().find(|e| same_device(&e.device, device))
+    }
+
+    /// The entry for `device`, also matching entries whose device resolves
+    /// to the same device node, so `/dev/sda1` finds `UUID=...` and back
+    pub fn find_device_resolved<E: Environment + ?Sized>(
+        &self,
+        device: &Device,
+        env: &E,
+    ) -> Option<&Fstab> {
+        if let Some(entry) = self.find_device(device) {
+            return Some(entry);
+        }
+        let node = env.resolve(device).ok()?;
+        self.entries
+            .iter()
+            .find(|e| env.resolve(&e.device).ok().as_ref() == Some(&node))
+    }
+
+    /// The entries of type `fs_type`, including those listing it among other types
+    pub fn with_type<'a>(&'a self, fs_type: &'a str) -> impl Iterator<Item = &'a Fstab> + 'a {
+        self.entries
+            .iter()
+            .filter(move |e| e.device_type.split(',').any(|t| t == fs_type))
+    }
+
+    /// The entries with an option called `name`, with or without a value
+    pub fn with_option<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Fstab> + 'a {
+        self.entries
+            .iter()
+            .filter(move |e| e.options.contains(name))
+    }
+
+    /// The entries whose type matches a type list, as `mount -t` takes it
+    ///
+    /// See [`match_fstype`](fn.match_fstype.html).
+    pub fn filter_types<'a>(&'a self, pattern: &'a str) -> impl Iterator<Item = &'a Fstab> + 'a {
+        self.entries
+            .iter()
+            .filter(move |e| match_fstype(&e.device_type, pattern))
+    }
+
+    /// The entries whose options match an option pattern, as `mount -O` takes it
+    ///
+    /// See [`match_options`](fn.match_options.html).
+    pub fn filter_options<'a>(&'a self, pattern: &'a str) -> impl Iterator<Item = &'a Fstab> + 'a {
+        self.entries
+            .iter()
+            .filter(move |e| match_options(&e.options, pattern))
+    }
+}
+
+impl From<Vec<Fstab>> for FstabTable {
+    fn from(entries: Vec<Fstab>) -> FstabTable {
+        FstabTable::new(entries)
+    }
+}
+
+impl FromIterator<Fstab> for FstabTable {
+    fn from_iter<I: IntoIterator<Item = Fstab>>(iter: I) -> FstabTable {
+        FstabTable::new(iter.into_iter().collect())
+    }
+}
+
+impl<'a> IntoIterator for &'a FstabTable {
+    type Item = &'a Fstab;
+    type IntoIter = slice::Iter<'a, Fstab>;
+
+    fn into_iter(self) -> slice::Iter<'a, Fstab> {
+        self.entries.iter()
+    }
+}
+
+impl IntoIterator for FstabTable {
+    type Item = Fstab;
+    type IntoIter = ::std::vec::IntoIter<Fstab>;
+
+    fn into_iter(self) -> ::std::vec::IntoIter<Fstab> {
+        self.entries.into_iter()
+    }
+}
+
+/// Paths are compared normalized; anything else must be equal
+fn same_device(a: &Device, b: &Device) -> bool {
+    match (a.path(), b.path()) {
+        (Some(a), Some(b)) => normalize_dir(a) == normalize_dir(b),
+        _ => a == b,
+    }
+}
+
+/// Returns `true` if `fs_type` matches a type list, with the semantics of
+/// `mount -t` in util-linux
+///
+/// The list is comma separated and compared without regard to case. A `no`
+/// prefix on the whole list negates it (`nonfs,cifs` is anything but NFS
+/// and CIFS), and a `no` prefix on a later item excludes that type
+/// (`ext4,noxfs`).
+pub fn match_fstype(fs_type: &str, pattern: &str) -> bool {
+    let (negated, pattern) = match pattern.strip_prefix("no") {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    for item in pattern.split(',') {
+        if item
+            .strip_prefix("no")
+            .map_or(false, |t| t.eq_ignore_ascii_case(fs_type))
+        {
+            return false;
+        }
+        if item.eq_ignore_ascii_case(fs_type) {
+            return !negated;
+        }
+    }
+    negated
+}
+
+/// Returns `true` if `options` match an option pattern, with the semantics
+/// of `mount -O` in util-linux
+///
+/// Every comma separated item of the pattern must match. An item matches if
+/// the option is present, or absent when the item has a `no` prefix
+/// (`no_netdev`); a `+` prefix takes the rest literally, so `+noauto`
+/// matches the `noauto` option. `name=value` also requires the value of the
+/// first option called `name` to be `value`. A bare `no` never matches.
+pub fn match_options(options: &MountOptions, pattern: &str) -> bool {
+    for item in pattern.split(',').filter(|i| !i.is_empty()) {
+        let (name, value) = match item.find('=') {
+            Some(i) => (&item[..i], Some(&item[i + 1..])),
+            None => (item, None),
+        };
+        let (negated, name) = if let Some(literal) = name.strip_prefix('+') {
+            (false, literal)
+        } else if let Some(rest) = name.strip_prefix("no") {
+            if rest.is_empty() {
+                return false;
+            }
+            (true, rest)
+        } else {
+            (false, name)
+        };
+        let found = name.is_empty()
+            || options.iter().find(|o| o.name == name).map_or(false, |o| {
+                value.map_or(true, |v| v.is_empty() || o.value.as_deref() == Some(v))
+            });
+        if found == negated {
+            return false;
+        }
+    }
+    true
+}
+
+#[test]
+fn table_lookups() {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use Resolver;
+
+    let table = FstabTable::from(
+        ::parse_fstab(concat!(
+            "UUID=0a1b / ext4 errors=remount-ro 0 1\n",
+            "/dev/sda2 /home/ ext4 defaults,noatime 0 2\n",
+            "nas:/vol /mnt/nas nfs4 _netdev,ro 0 0\n",
+            "LABEL=media /media ext4,xfs noauto 0 0\n",
+            "tmpfs /tmp tmpfs nosuid,size=1G 0 0\n",
+        ))
+        .unwrap(),
+    );
+    assert_eq!(table.len(), 5);
+    assert_eq!(table.find_mount_point("/home").unwrap().fsck, 2);
+    assert_eq!(
+        table.find_mount_point("//tmp/./").unwrap().device_type,
+        "tmpfs"
+    );
+    assert!(table.find_mount_point("/var").is_none());
+
+    let dev = |s: &str| s.parse::<Device>().unwrap();
+    assert_eq!(table.find_device(&dev("UUID=0a1b")).unwrap().dir, "/");
+    assert_eq!(table.find_device(&dev("/dev//sda2")).unwrap().dir, "/home/");
+    assert!(table.find_device(&dev("/dev/sda1")).is_none());
+
+    let root = ::test_dir("table");
+    fs::create_dir_all(root.join("dev/disk/by-uuid")).unwrap();
+    fs::write(root.join("dev/sda1"), b"").unwrap();
+    symlink("../../sda1", root.join("dev/disk/by-uuid/0a1b")).unwrap();
+    let resolver = Resolver::with_root(&root).with_probing(false);
+    assert_eq!(
+        table
+            .find_device_resolved(&dev("/dev/sda1"), &resolver)
+            .unwrap()
+            .dir,
+        "/"
+    );
+    assert!(table
+        .find_device_resolved(&dev("/dev/sdz"), &resolver)
+        .is_none());
+    fs::remove_dir_all(&root).unwrap();
+
+    let dirs = |it: &mut dyn Iterator<Item = &Fstab>| it.map(|e| e.dir.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        dirs(&mut table.with_type("ext4")),
+        ["/", "/home/", "/media"]
+    );
+    assert_eq!(dirs(&mut table.with_type("xfs")), ["/media"]);
+    assert_eq!(dirs(&mut table.with_option("noatime")), ["/home/"]);
+    assert_eq!(
+        dirs(&mut table.filter_types("nonfs4,tmpfs")),
+        ["/", "/home/", "/media"]
+    );
+    assert_eq!(
+        dirs(&mut table.filter_types("NFS4,tmpfs")),
+        ["/mnt/nas", "/tmp"]
+    );
+    assert_eq!(
+        dirs(&mut table.filter_options("no_netdev,noerrors")),
+        ["/home/", "/media", "/tmp"]
+    );
+    assert_eq!(dirs(&mut table.filter_options("+noauto")), ["/media"]);
+}
+
+#[test]
+fn util_linux_type_lists() {
+    assert!(match_fstype("ext4", "ext4"));
+    assert!(match_fstype("ext4", "EXT4"));
+    assert!(match_fstype("ext4", "xfs,ext4"));
+    assert!(!match_fstype("ext", "ext4"));
+    assert!(!match_fstype("nfs", "nonfs"));
+    assert!(match_fstype("xfs", "nonfs"));
+    // The prefix negates the whole list
+    assert!(!match_fstype("ext4", "nonfs,ext4"));
+    assert!(match_fstype("xfs", "nonfs,ext4"));
+    // ... and on later items excludes a single type
+    assert!(!match_fstype("nfs", "ext4,nonfs"));
+    assert!(!match_fstype("xfs", "ext4,nonfs"));
+    assert!(match_fstype("ext4", "ext4,nonfs"));
+    assert!(!match_fstype("xfs", "nonfs,noxfs"));
+}
+
+#[test]
+fn util_linux_option_patterns() {
+    let options = MountOptions::from("rw,noauto,uid=1000,user=alice,_netdev");
+    assert!(match_options(&options, "rw"));
+    assert!(match_options(&options, "rw,_netdev"));
+    assert!(!match_options(&options, "rw,ro"));
+    assert!(match_options(&options, "noro"));
+    assert!(!match_options(&options, "no_netdev"));
+    assert!(match_options(&options, "+noauto"));
+    assert!(!match_options(&options, "auto"));
+    // Without `+`, the prefix asks for `auto` to be absent
+    assert!(match_options(&options, "noauto"));
+    assert!(match_options(&options, "uid=1000"));
+    assert!(!match_options(&options, "uid=0"));
+    assert!(match_options(&options, "nouid=0"));
+    assert!(match_options(&options, "uid="));
+    assert!(!match_options(&options, "rw=1"));
+    assert!(!match_options(&options, "no"));
+    assert!(match_options(&options, ""));
+    assert!(match_options(&MountOptions::new(), ""));
+    assert!(!match_options(&MountOptions::new(), "rw"));
+    assert!(match_options(&MountOptions::new(), "norw"));
+}
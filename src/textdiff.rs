@@ -0,0 +1,124 @@
+/// Lines of context around each change, as with `diff -u`
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// A unified diff between two texts, or an empty string if they are equal
+///
+/// Lines are compared exactly; a missing newline at the end of either text
+/// is marked the way diff(1) does.
+pub(crate) fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let a = old.split_inclusive('\n').collect::<Vec<_>>();
+    let b = new.split_inclusive('\n').collect::<Vec<_>>();
+    let ops = diff_lines(&a, &b);
+    if ops.iter().all(|&(op, _, _)| op == Op::Equal) {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    let changes = ops
+        .iter()
+        .enumerate()
+        .filter(|&(_, &(op, _, _))| op != Op::Equal)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let mut i = 0;
+    while i < changes.len() {
+        // Extend the hunk while the next change is close enough to share context
+        let start = changes[i].saturating_sub(CONTEXT);
+        let mut last = changes[i];
+        while i + 1 < changes.len() && changes[i + 1] - last <= 2 * CONTEXT {
+            i += 1;
+            last = changes[i];
+        }
+        let end = (last + CONTEXT + 1).min(ops.len());
+        i += 1;
+
+        let hunk = &ops[start..end];
+        let old_count = hunk.iter().filter(|o| o.0 != Op::Insert).count();
+        let new_count = hunk.iter().filter(|o| o.0 != Op::Delete).count();
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(hunk[0].1, old_count),
+            range(hunk[0].2, new_count)
+        ));
+        for &(op, ai, bi) in hunk {
+            let (prefix, line) = match op {
+                Op::Equal => (' ', a[ai]),
+                Op::Delete => ('-', a[ai]),
+                Op::Insert => ('+', b[bi]),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// A hunk range: the first line and the count, which is left out when it is 1
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// The edit script turning `a` into `b`, as operations with the positions
+/// in both texts they apply at
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<(Op, usize, usize)> {
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::with_capacity(a.len() + b.len());
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push((Op::Equal, i, j));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push((Op::Delete, i, j));
+            i += 1;
+        } else {
+            ops.push((Op::Insert, i, j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+#[test]
+fn unified_text_diff() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+    let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nk\nl";
+    assert_eq!(
+        unified_diff(old, new, "old", "new"),
+        concat!(
+            "--- old\n+++ new\n",
+            "@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n",
+            "@@ -7,5 +7,5 @@\n g\n h\n i\n-j\n k\n+l\n\\ No newline at end of file\n",
+        )
+    );
+    assert_eq!(unified_diff(old, old, "old", "new"), "");
+    assert_eq!(
+        unified_diff("", "x\n", "old", "new"),
+        "--- old\n+++ new\n@@ -0,0 +1 @@\n+x\n"
+    );
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use {normalize_dir, Device, Fstab, ResolveError, Resolver};
+
+/// Types that are valid in fstab without being a kernel filesystem
+const SPECIAL_TYPES: &[&str] = &["auto", "swap", "none", "ignore"];
+
+/// Types that have nothing on disk for fsck to check
+const UNCHECKABLE_TYPES: &[&str] = &[
+    "swap", "none", "ignore", "tmpfs", "ramfs", "proc", "sysfs", "devpts", "devtmpfs", "nfs",
+    "nfs4", "cifs", "smb3",
+];
+
+/// Where fsck(8) looks for its `fsck.<type>` helpers
+const FSCK_DIRS: &[&str] = &["/sbin", "/usr/sbin"];
+
+/// The lookups needed to verify entries against a system
+///
+/// `Resolver` implements it for the running system, or for the tree below
+/// its root; fixtures can implement it to verify entries in tests.
+pub trait Environment {
+    /// Whether `path` is a directory, or `None` if it does not exist
+    fn is_dir(&self, path: &str) -> Option<bool>;
+
+    /// Resolve `device` to its device node
+    fn resolve(&self, device: &Device) -> Result<PathBuf, ResolveError>;
+
+    /// The filesystem types the kernel supports, as listed in `/proc/filesystems`
+    fn filesystems(&self) -> Vec<String>;
+
+    /// Returns `true` if there is a `fsck.<fs_type>` helper
+    fn has_fsck(&self, fs_type: &str) -> bool;
+}
+
+impl Environment for Resolver {
+    fn is_dir(&self, path: &str) -> Option<bool> {
+        fs::metadata(self.host_path(Path::new(path)))
+            .ok()
+            .map(|m| m.is_dir())
+    }
+
+    fn resolve(&self, device: &Device) -> Result<PathBuf, ResolveError> {
+        Resolver::resolve(self, device)
+    }
+
+    fn filesystems(&self) -> Vec<String> {
+        // Lines are `nodev\tsysfs` or `\text4`
+        fs::read_to_string(self.host_path(Path::new("/proc/filesystems")))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.split_whitespace().last())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    fn has_fsck(&self, fs_type: &str) -> bool {
+        FSCK_DIRS.iter().any(|dir| {
+            let helper = Path::new(dir).join(format!("fsck.{}", fs_type));
+            self.host_path(&helper).exists()
+        })
+    }
+}
+
+/// What is wrong with an entry
+///
+/// With the `serde` feature, it is serialized as
+/// `{"kind": "mount_point_not_exist", "detail": "/data"}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "kind", content = "detail", rename_all = "snake_case")
+)]
+pub enum ProblemKind {
+    /// The mount point does not exist
+    MountPointNotExist(String),
+    /// The mount point exists but is not a directory
+    NotADirectory(String),
+    /// No device node was found for the device
+    DeviceNotFound(Device),
+    /// The filesystem type is not supported by the running kernel
+    UnsupportedFsType(String),
+    /// Another entry has the same mount point
+    DuplicateMountPoint(String),
+    /// `fsck` is set for a filesystem that can not be checked
+    NotCheckable(String),
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProblemKind::MountPointNotExist(ref s) => {
+                write!(f, "mount point does not exist: {}", s)
+            }
+            ProblemKind::NotADirectory(ref s) => write!(f, "mount point is not a directory: {}", s),
+            ProblemKind::DeviceNotFound(ref d) => write!(f, "device not found: {}", d),
+            ProblemKind::UnsupportedFsType(ref s) => {
+                write!(f, "filesystem type not supported by the kernel: {}", s)
+            }
+            ProblemKind::DuplicateMountPoint(ref s) => write!(f, "duplicate mount point: {}", s),
+            ProblemKind::NotCheckable(ref s) => {
+                write!(f, "fsck set on a filesystem that can not be checked: {}", s)
+            }
+        }
+    }
+}
+
+/// A problem found with an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Problem {
+    /// Index of the entry in the verified list
+    pub index: usize,
+    /// What is wrong with it
+    pub kind: ProblemKind,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+/// Verify entries against the running system
+///
+/// See [`verify`](fn.verify.html) for what is checked.
+pub fn check(entries: &[Fstab]) -> Vec<Problem> {
+    verify(entries, &Resolver::new())
+}
+
+/// Verify entries against the system seen through `env`
+///
+/// Reports mount points that do not exist or are not directories, tags
+/// that do not resolve to a device, types the kernel does not list in
+/// `/proc/filesystems`, mount points used more than once, and a `fsck`
+/// pass on filesystems that can not be checked.
+///
+/// Mount points with `X-mount.mkdir` are created by mount(8) and may be
+/// missing. Types of modules that are not loaded yet are not listed by the
+/// kernel, and the type check is skipped when the list is empty.
+pub fn verify<E: Environment + ?Sized>(entries: &[Fstab], env: &E) -> Vec<Problem> {
+    let filesystems = env.filesystems();
+    let mut seen = HashMap::new();
+    let mut problems = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let mut report = |kind| problems.push(Problem { index, kind });
+        let dir = normalize_dir(&entry.dir);
+        if entry.device_type != "swap" && dir.starts_with('/') {
+            if seen.insert(dir.clone(), index).is_some() {
+                report(ProblemKind::DuplicateMountPoint(dir.clone()));
+            }
+            if !entry.options.contains("X-mount.mkdir") {
+                match env.is_dir(&dir) {
+                    None => report(ProblemKind::MountPointNotExist(dir.clone())),
+                    Some(false) => report(ProblemKind::NotADirectory(dir.clone())),
+                    Some(true) => {}
+                }
+            }
+        }
+        if entry.device.is_tag() && env.resolve(&entry.device).is_err() {
+            report(ProblemKind::DeviceNotFound(entry.device.clone()));
+        }
+        if !filesystems.is_empty() {
+            for fs_type in entry.device_type.split(',') {
+                // Subtypes like fuse.sshfs are listed as their main type
+                let main = fs_type.split('.').next().unwrap_or(fs_type);
+                if !SPECIAL_TYPES.contains(&main) && !filesystems.iter().any(|f| f == main) {
+                    report(ProblemKind::UnsupportedFsType(fs_type.to_owned()));
+                }
+            }
+        }
+        if entry.fsck > 0 && !is_checkable(entry, env) {
+            report(ProblemKind::NotCheckable(entry.device_type.clone()));
+        }
+    }
+    problems
+}
+
+/// Returns `true` if fsck(8) can check the filesystem of `entry`
+fn is_checkable<E: Environment + ?Sized>(entry: &Fstab, env: &E) -> bool {
+    let fs_type = entry.device_type.as_str();
+    can_fsck(entry) && (fs_type == "auto" || env.has_fsck(fs_type))
+}
+
+/// Returns `false` for network, pseudo and other filesystems that fsck never
+/// checks, whatever helpers are installed
+pub(crate) fn can_fsck(entry: &Fstab) -> bool {
+    !entry.device.is_network()
+        && !entry.device.is_pseudo()
+        && !UNCHECKABLE_TYPES.contains(&entry.device_type.as_str())
+}
+
+#[cfg(test)]
+struct Fixture {
+    dirs: &'static [&'static str],
+    files: &'static [&'static str],
+    devices: &'static [&'static str],
+    filesystems: &'static [&'static str],
+    fsck: &'static [&'static str],
+}
+
+#[cfg(test)]
+impl Environment for Fixture {
+    fn is_dir(&self, path: &str) -> Option<bool> {
+        if self.dirs.contains(&path) {
+            Some(true)
+        } else if self.files.contains(&path) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn resolve(&self, device: &Device) -> Result<PathBuf, ResolveError> {
+        if self.devices.contains(&device.to_string().as_str()) {
+            Ok(PathBuf::from("/dev/sda1"))
+        } else {
+            Err(ResolveError::NotFound(device.clone()))
+        }
+    }
+
+    fn filesystems(&self) -> Vec<String> {
+        self.filesystems.iter().map(|f| f.to_string()).collect()
+    }
+
+    fn has_fsck(&self, fs_type: &str) -> bool {
+        self.fsck.contains(&fs_type)
+    }
+}
+
+#[test]
+fn verify_entries() {
+    let env = Fixture {
+        dirs: &["/", "/home", "/tmp", "/mnt/nas"],
+        files: &["/srv/file"],
+        devices: &["UUID=0a1b2c3d", "LABEL=home"],
+        filesystems: &["ext4", "tmpfs", "nfs", "fuse", "proc"],
+        fsck: &["ext4"],
+    };
+    let entries = ::parse_fstab(concat!(
+        "UUID=0a1b2c3d / ext4 defaults 0 1\n",
+        "LABEL=home /home/ ext4 defaults 0 2\n",
+        "UUID=ffff none swap sw 0 0\n",
+        "tmpfs /tmp tmpfs defaults 0 2\n",
+        "nas:/export /mnt/nas nfs defaults 0 0\n",
+        "/dev/sdb1 /srv/file xfs defaults 0 0\n",
+        "sshfs#me@host: /mnt/missing fuse.sshfs defaults 0 0\n",
+        "/dev/sdc1 /mnt/new ext4 X-mount.mkdir 0 0\n",
+        "LABEL=home /home ext4 defaults 0 2\n",
+    ))
+    .unwrap();
+    let problems = verify(&entries, &env)
+        .iter()
+        .map(|p| (p.index, p.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        problems,
+        vec![
+            (2, "device not found: UUID=ffff".to_owned()),
+            (
+                3,
+                "fsck set on a filesystem that can not be checked: tmpfs".to_owned()
+            ),
+            (5, "mount point is not a directory: /srv/file".to_owned()),
+            (
+                5,
+                "filesystem type not supported by the kernel: xfs".to_owned()
+            ),
+            (6, "mount point does not exist: /mnt/missing".to_owned()),
+            (8, "duplicate mount point: /home".to_owned()),
+        ]
+    );
+
+    assert_eq!(
+        verify(&entries[2..3], &env),
+        [Problem {
+            index: 0,
+            kind: ProblemKind::DeviceNotFound(Device::Uuid("ffff".to_owned())),
+        }]
+    );
+
+    let env = Fixture {
+        filesystems: &[],
+        ..env
+    };
+    assert_eq!(verify(&entries[5..6], &env).len(), 1);
+}
+
+#[test]
+fn verify_with_root() {
+    let root = ::test_dir("verify");
+    fs::create_dir_all(root.join("proc")).unwrap();
+    fs::create_dir_all(root.join("sbin")).unwrap();
+    fs::create_dir_all(root.join("home")).unwrap();
+    fs::write(root.join("proc/filesystems"), "nodev\tsysfs\n\text4\n").unwrap();
+    fs::write(root.join("sbin/fsck.ext4"), b"").unwrap();
+
+    let entries = ::parse_fstab(concat!(
+        "/dev/sda2 /home ext4 defaults 0 2\n",
+        "/dev/sda3 /data btrfs defaults 0 2\n",
+    ))
+    .unwrap();
+    let problems = verify(&entries, &Resolver::with_root(&root))
+        .iter()
+        .map(|p| (p.index, p.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        problems,
+        vec![
+            (1, "mount point does not exist: /data".to_owned()),
+            (
+                1,
+                "filesystem type not supported by the kernel: btrfs".to_owned()
+            ),
+            (
+                1,
+                "fsck set on a filesystem that can not be checked: btrfs".to_owned()
+            ),
+        ]
+    );
+    fs::remove_dir_all(&root).unwrap();
+}